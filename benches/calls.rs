@@ -0,0 +1,26 @@
+// times call-heavy programs on the tree-walker and the virtual machine, run with `cargo bench`
+use equa::{Engine, interpreter::Context, builtins, vm};
+use std::time::{Duration, Instant};
+
+const PROGRAMS: [(&str, &str); 3] = [
+    ("fib(24)", "fib := n -> n < 2 ? n : fib(n - 1) + fib(n - 2); fib(24)"),
+    ("tail loop", "count := (n, acc := 0) -> n = 0 ? acc : count(n - 1, acc + n); count(200000)"),
+    ("named and defaults", "f := (x, y := 1) -> x + y; g := n -> n = 0 ? 0 : f(n, y: 2) + g(n - 1); g(2000)"),
+];
+const RUNS: u32 = 5;
+
+fn best(f: impl Fn()) -> Duration {
+    (0..RUNS).map(|_| { let start = Instant::now(); f(); start.elapsed() }).min().unwrap()
+}
+
+fn main() {
+    for (name, text) in PROGRAMS {
+        let interpreted = best(|| { std::hint::black_box(Engine::new().eval(text).unwrap()); });
+        let compiled = best(|| {
+            let mut context = Context::new();
+            builtins::std_context(&mut context).unwrap();
+            std::hint::black_box(vm::run(text, &mut context, "bench").unwrap());
+        });
+        println!("{name:<20} interpreter {:>8.2?}   vm {:>8.2?}", interpreted, compiled);
+    }
+}
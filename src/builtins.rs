@@ -0,0 +1,257 @@
+use crate::set::*;
+use crate::value::*;
+use crate::error::*;
+use crate::interpreter::*;
+use crate::native::*;
+use crate::numeric;
+use crate::latex;
+use std::{rc::Rc, cell::RefCell, collections::HashMap};
+
+fn rounded(num: Number, f: fn(f64) -> f64) -> Number {
+    match num {
+        Number::Int(v) => Number::Int(v),
+        Number::Float(v) => Number::Float(f(v)),
+    }
+}
+fn collect(like: &Value, values: Vec<Value>) -> Value {
+    match like {
+        Value::Set(_) => {
+            let mut set: Set<Value> = Set::new();
+            for v in values { set.add(v) }
+            Value::Set(set.into())
+        }
+        Value::Tuple(_) => Value::Tuple(values.into()),
+        _ => Value::Vector(values.into()),
+    }
+}
+fn function(value: &Value) -> Result<Value, Error> {
+    if !value.callable() { return Err(Error::ExpectedType(Type::Function, value.typ())) }
+    Ok(value.clone())
+}
+pub fn _solve(args: &[Value], context: &mut Context, path: &str) -> Result<Value, Error> {
+    let (a, b): (f64, Option<f64>) = (arg(args, 1)?, arg(args, 2)?);
+    let mut f = numeric::function(&args[0], context, path);
+    let root = match b {
+        Some(b) => numeric::brent(&mut f, a, b, numeric::TOLERANCE)?,
+        None => numeric::newton(&mut f, a, numeric::TOLERANCE)?,
+    };
+    root.into_value()
+}
+pub fn _roots(args: &[Value], context: &mut Context, path: &str) -> Result<Value, Error> {
+    let (a, b): (f64, f64) = (arg(args, 1)?, arg(args, 2)?);
+    let mut f = numeric::function(&args[0], context, path);
+    let mut roots: Set<Value> = Set::new();
+    for root in numeric::roots(&mut f, a, b, 1000, numeric::TOLERANCE)? {
+        roots.add(Value::Number(Number::Float(root)));
+    }
+    roots.into_value()
+}
+pub fn _integrate(args: &[Value], context: &mut Context, path: &str) -> Result<Value, Error> {
+    let (a, b, tol): (f64, f64, Option<f64>) = (arg(args, 1)?, arg(args, 2)?, arg(args, 3)?);
+    let mut f = numeric::function(&args[0], context, path);
+    numeric::integrate(&mut f, a, b, tol.unwrap_or(1e-10)).into_value()
+}
+pub fn _map(args: &[Value], context: &mut Context, path: &str) -> Result<Value, Error> {
+    let mut values: Vec<Value> = vec![];
+    for v in arg::<Vec<Value>>(args, 1)? {
+        values.push(call(&args[0], vec![v], context, path)?);
+    }
+    Ok(collect(&args[1], values))
+}
+pub fn _filter(args: &[Value], context: &mut Context, path: &str) -> Result<Value, Error> {
+    let mut values: Vec<Value> = vec![];
+    for v in arg::<Vec<Value>>(args, 1)? {
+        if call(&args[0], vec![v.clone()], context, path)?.truthy() {
+            values.push(v);
+        }
+    }
+    Ok(collect(&args[1], values))
+}
+pub fn _fold(args: &[Value], context: &mut Context, path: &str) -> Result<Value, Error> {
+    let mut acc = args[1].clone();
+    for v in arg::<Vec<Value>>(args, 2)? {
+        acc = call(&args[0], vec![acc, v], context, path)?;
+    }
+    Ok(acc)
+}
+pub fn _zip(args: &[Value], _context: &mut Context, _path: &str) -> Result<Value, Error> {
+    let (a, b): (Vec<Value>, Vec<Value>) = (arg(args, 0)?, arg(args, 1)?);
+    let values = a.into_iter().zip(b).map(|(x, y)| Value::Tuple(vec![x, y].into())).collect();
+    Ok(collect(&args[0], values))
+}
+pub fn _any(args: &[Value], context: &mut Context, path: &str) -> Result<Value, Error> {
+    for v in arg::<Vec<Value>>(args, 1)? {
+        if call(&args[0], vec![v], context, path)?.truthy() { return true.into_value() }
+    }
+    false.into_value()
+}
+pub fn _all(args: &[Value], context: &mut Context, path: &str) -> Result<Value, Error> {
+    for v in arg::<Vec<Value>>(args, 1)? {
+        if !call(&args[0], vec![v], context, path)?.truthy() { return false.into_value() }
+    }
+    true.into_value()
+}
+pub fn _const(x: Value) -> Value {
+    Value::Partial(Rc::new(typed(&["x", "y"], |x: Value, _: Value| x)), vec![x].into(), vec![].into())
+}
+pub fn _flip(f: Value) -> Result<Value, Error> {
+    let flipped = native(&["f", "a", "b"], |args, context, path| call(&args[0], vec![args[2].clone(), args[1].clone()], context, path));
+    Ok(Value::Partial(Rc::new(flipped), vec![function(&f)?].into(), vec![].into()))
+}
+pub fn _memo(f: Value) -> Result<Value, Error> {
+    Ok(Value::Memo(Rc::new(function(&f)?), Rc::new(RefCell::new(HashMap::new()))))
+}
+pub fn _forget(f: Value) -> Result<Value, Error> {
+    if let Value::Memo(_, cache) = &f {
+        let len = cache.borrow().len();
+        cache.borrow_mut().clear();
+        return (len as i64).into_value()
+    }
+    function(&f)?;
+    0i64.into_value()
+}
+/// Defines the standard functions and constants, failing if the context already binds one of them.
+pub fn std_context(context: &mut Context) -> Result<(), Error> {
+    let functions = [
+        ("sqrt", typed(&["x"], f64::sqrt)),
+        ("floor", typed(&["x"], |x: Number| rounded(x, f64::floor))),
+        ("ceil", typed(&["x"], |x: Number| rounded(x, f64::ceil))),
+        ("round", typed(&["x"], |x: Number| rounded(x, f64::round))),
+        ("abs", typed(&["x"], |x: Number| match x { Number::Int(v) => Number::Int(v.abs()), Number::Float(v) => Number::Float(v.abs()) })),
+        ("sin", typed(&["x"], f64::sin)),
+        ("cos", typed(&["x"], f64::cos)),
+        ("tan", typed(&["x"], f64::tan)),
+        ("asin", typed(&["x"], f64::asin)),
+        ("acos", typed(&["x"], f64::acos)),
+        ("atan", typed(&["x"], f64::atan)),
+        ("atan2", typed(&["x", "y"], f64::atan2)),
+        ("sinh", typed(&["x"], f64::sinh)),
+        ("cosh", typed(&["x"], f64::cosh)),
+        ("tanh", typed(&["x"], f64::tanh)),
+        ("asinh", typed(&["x"], f64::asinh)),
+        ("acosh", typed(&["x"], f64::acosh)),
+        ("atanh", typed(&["x"], f64::atanh)),
+        ("solve", native(&[Param::new("f"), Param::new("a"), Param::optional("b")], _solve)),
+        ("roots", native(&["f", "a", "b"], _roots)),
+        ("integrate", native(&[Param::new("f"), Param::new("a"), Param::new("b"), Param::optional("tol")], _integrate)),
+        ("map", native(&["f", "v"], _map)),
+        ("filter", native(&["p", "v"], _filter)),
+        ("fold", native(&["f", "init", "v"], _fold)),
+        ("zip", native(&["a", "b"], _zip)),
+        ("any", native(&["p", "v"], _any)),
+        ("all", native(&["p", "v"], _all)),
+        ("id", typed(&["x"], |x: Value| x)),
+        ("const", typed(&["x"], _const)),
+        ("flip", typed(&["f"], _flip)),
+        ("memo", typed(&["f"], _memo)),
+        ("forget", typed(&["f"], _forget)),
+        ("latex", typed(&["x"], |x: Value| Value::Str(latex::value(&x)))),
+    ];
+    for (id, function) in functions {
+        context.global_const(&id.to_string(), &function)?;
+    }
+    context.global_const(&"PI".to_string(), &Value::Number(Number::Float(std::f64::consts::PI)))?;
+    context.global_const(&"TAU".to_string(), &Value::Number(Number::Float(std::f64::consts::TAU)))?;
+    context.global_const(&"E".to_string(), &Value::Number(Number::Float(std::f64::consts::E)))?;
+    context.global_const(&"inf".to_string(), &Value::Number(Number::Float(f64::INFINITY)))?;
+    context.global_const(&"NaN".to_string(), &Value::Number(Number::Float(f64::NAN)))
+}
+#[cfg(test)]
+mod tests {
+    use crate::Engine;
+    use crate::error::Error;
+    use crate::value::*;
+
+    fn eval(text: &str) -> Result<Value, Error> {
+        Engine::new().eval(text).map(|v| v.unwrap())
+    }
+    // the message without the traceback
+    fn error(text: &str) -> String {
+        eval(text).unwrap_err().to_string().lines().next().unwrap().to_string()
+    }
+    fn float(text: &str) -> f64 {
+        match eval(text) {
+            Ok(Value::Number(v)) => v.to_float(),
+            res => panic!("{text}: {res:?}")
+        }
+    }
+
+    #[test]
+    fn std_context_reports_names_already_bound() {
+        let mut context = crate::interpreter::Context::new();
+        context.global_const(&"PI".to_string(), &Value::Number(Number::Int(3))).unwrap();
+        assert_eq!(super::std_context(&mut context).unwrap_err().to_string(), Error::AlreadyDefined("PI".to_string()).to_string());
+    }
+    #[test]
+    fn solve_with_and_without_bracket() {
+        assert!((float("solve(x -> x^2 - 2, 1)") - 2f64.sqrt()).abs() < 1e-9);
+        assert!((float("solve(x -> x^2 - 2, 0, 2)") - 2f64.sqrt()).abs() < 1e-9);
+        assert!((float("solve(x -> x^2 - 2, 0, b: 2)") - 2f64.sqrt()).abs() < 1e-9);
+    }
+    #[test]
+    fn solve_arity_counts_the_optional_bound() {
+        assert_eq!(error("solve(x -> x)"), "ERROR: expected 2 to 3 arguments, got 1");
+        assert_eq!(error("solve(x -> x, 1, 2, 3)"), "ERROR: expected 2 to 3 arguments, got 4");
+    }
+    #[test]
+    fn roots_returns_a_set() {
+        assert_eq!(eval("roots(x -> x^2 - 4, -5, 5)").unwrap().to_string(), "{-2 2}");
+        assert_eq!(eval("roots(x -> tan(x), 1, 2)").unwrap().to_string(), "{}");
+    }
+    #[test]
+    fn printed_natives_mark_optional_parameters() {
+        assert_eq!(eval("solve").unwrap().to_string(), "foreign-function(f, a, b?)");
+    }
+    #[test]
+    fn natives_pass_the_callers_path_on() {
+        let mut engine = Engine::new();
+        engine.register_native("source", &["x"], |_, _, path| Ok(Value::Str(path.to_string()))).unwrap();
+        assert_eq!(engine.eval("map(x -> source(x), [1])").unwrap().unwrap().to_string(), "[<engine>]");
+    }
+    #[test]
+    fn integrate_with_an_optional_tolerance() {
+        assert!((float("integrate(x -> x^2, 0, 3)") - 9.0).abs() < 1e-9);
+        assert!((float("integrate(x -> E^(-x), 0, inf, tol: 0.00000001)") - 1.0).abs() < 1e-6);
+        assert_eq!(error("integrate(x -> x, 0, 1, 0)"), "ERROR: tolerance must be positive, got 0");
+    }
+    #[test]
+    fn map_and_filter_keep_the_collection_kind() {
+        assert_eq!(eval("map(x -> x * 2, [1 2 3])").unwrap().to_string(), "[2 4 6]");
+        assert_eq!(eval("map(x -> x * 0, {1 2 3})").unwrap().to_string(), "{0}");
+        assert_eq!(eval("map(x -> x + 1, (1, 2))").unwrap().to_string(), "(2, 3)");
+        assert_eq!(eval("filter(x -> x > 1, [1 2 3])").unwrap().to_string(), "[2 3]");
+        assert_eq!(eval("filter(x -> x > 1, {1 2 3})").unwrap().to_string(), "{2 3}");
+    }
+    #[test]
+    fn fold_zip_any_all() {
+        assert_eq!(eval("fold((a, x) -> a + x, 0, [1 2 3 4])").unwrap().to_string(), "10");
+        assert_eq!(eval("fold((a, x) -> a + x, 7, [])").unwrap().to_string(), "7");
+        assert_eq!(eval("zip([1 2 3], [4 5])").unwrap().to_string(), "[(1, 4) (2, 5)]");
+        assert_eq!(eval("any(x -> x > 2, [1 2 3])").unwrap().to_string(), "1");
+        assert_eq!(eval("any(x -> x > 3, [1 2 3])").unwrap().to_string(), "0");
+        assert_eq!(eval("all(x -> x > 0, [1 2 3])").unwrap().to_string(), "1");
+        assert_eq!(eval("all(x -> x > 1, [])").unwrap().to_string(), "1");
+    }
+    #[test]
+    fn higher_order_builtins_check_their_arguments() {
+        assert_eq!(error("map(x -> x, 3)"), "ERROR: expected type vector|set|tuple, got number");
+        assert_eq!(error("map(3, [1])"), "ERROR: expected type function, got number");
+    }
+    #[test]
+    fn memo_makes_recursion_linear() {
+        assert_eq!(eval("fib := memo(n -> n < 2 ? n : fib(n - 1) + fib(n - 2)); fib(40)").unwrap().to_string(), "102334155");
+    }
+    #[test]
+    fn memo_caches_by_value() {
+        let mut engine = Engine::new();
+        engine.eval("count := memo(x -> x)").unwrap();
+        assert_eq!(engine.eval("count([1 2]); count([1 2]); count({3}); count(1.5); forget(count)").unwrap().unwrap().to_string(), "3");
+        assert_eq!(engine.eval("forget(count)").unwrap().unwrap().to_string(), "0");
+    }
+    #[test]
+    fn forget_accepts_plain_functions() {
+        assert_eq!(eval("forget(x -> x)").unwrap().to_string(), "0");
+        assert_eq!(error("forget(1)"), "ERROR: expected type function, got number");
+        assert_eq!(error("memo(1)"), "ERROR: expected type function, got number");
+    }
+}
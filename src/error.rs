@@ -1,46 +1,236 @@
-use crate::lexer::*;
-use crate::parser::*;
-use crate::value::*;
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum Error {
-    Todo(String),
-    Char(String),
-    UnexpectedToken(Token),
-    UnexpectedNode(Node),
-    ExpectedToken(Token),
-    ExpectedType(Type, Type),
-    ExpectedTypes(Vec<Type>, Type),
-    InvalidBinaryOperation(Token),
-    InvalidUnaryOperation(Token),
-    IllegalBinaryOperation(Token, Value, Value),
-    IllegalUnaryOperation(Token, Value),
-    Immutable(String),
-    AlreadyDefined(String),
-    NotDefined(String),
-    TooFewArgs(usize, usize),
-}
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Todo(s) => write!(f, "ERROR: todo - {s}"),
-            Self::Char(s) => write!(f, "ERROR: unexpected '{s}'"),
-            Self::UnexpectedToken(t) => write!(f, "ERROR: unexpected {}", t.name()),
-            Self::UnexpectedNode(n) => write!(f, "ERROR: unexpected {n}"),
-            Self::ExpectedToken(t) => write!(f, "ERROR: expected {}", t.name()),
-            Self::ExpectedType(t1, t2) => write!(f, "ERROR: expected type {t1}, got {t2}"),
-            Self::ExpectedTypes(ts, t2) => write!(f, "ERROR: expected type {}, got {t2}",
-            ts.iter().map(|x| x.to_string()).collect::<Vec<String>>().join("|")),
-            Self::InvalidBinaryOperation(t) => write!(f, "ERROR: invalid binary operation {}", t.name()),
-            Self::InvalidUnaryOperation(t) => write!(f, "ERROR: invalid unary operation {}", t.name()),
-            Self::IllegalBinaryOperation(t, v1, v2) => write!(f, "ERROR: illegal binary operation {} between {} and {}",
-            t.name(), v1.typ(), v2.typ()),
-            Self::IllegalUnaryOperation(t, v) => write!(f, "ERROR: illegal unary operation {} on {}",
-            t.name(), v.typ()),
-            Self::Immutable(s) => write!(f, "ERROR: {s} is immutable"),
-            Self::AlreadyDefined(s) => write!(f, "ERROR: {s} is already defined"),
-            Self::NotDefined(s) => write!(f, "ERROR: {s} is not defined"),
-            Self::TooFewArgs(expect, recved) => write!(f, "ERROR: expected length of {expect} for the arguments, got length of {recved}"),
-        }
-    }
-}
\ No newline at end of file
+use crate::lexer::*;
+use crate::parser::*;
+use crate::value::*;
+use crate::position::*;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Todo(String),
+    Char(String),
+    UnexpectedToken(Token),
+    UnexpectedNode(Node),
+    ExpectedToken(Token),
+    ExpectedType(Type, Type),
+    ExpectedTypes(Vec<Type>, Type),
+    ExpectedInt(Value),
+    InvalidBinaryOperation(Token),
+    InvalidUnaryOperation(Token),
+    IllegalBinaryOperation(Token, Value, Value),
+    IllegalUnaryOperation(Token, Value),
+    Immutable(String),
+    AlreadyDefined(String),
+    NotDefined(String),
+    Arity(usize, Option<usize>, usize),
+    DuplicateArg(String),
+    UnknownArg(String),
+    SkippedArg(String, String),
+    NoConvergence(String, usize),
+    NoSignChange(f64, f64),
+    SubdivisionLimit(usize),
+    Tolerance(f64),
+    RecursionLimit(usize),
+    OutOfFuel(u64),
+    Timeout(Duration),
+    TooLarge(usize),
+    Json(String),
+    Import(String, String),
+    ImportCycle(String),
+    NoField(String, String),
+    UnknownCommand(String),
+    UnknownMode(String),
+    At(Box<Error>, Position),
+    Many(Vec<Error>),
+    Trace(Box<Error>, Vec<Frame>),
+}
+/// An entry of a traceback, innermost first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Call { name: Option<String>, pos: Position, args: Vec<Value>, named: Vec<(String, Value)> },
+    /// calls from the same site as the frames around it that aren't kept
+    Repeated(usize),
+    /// calls in tail position, their frames were reused by the ones after them
+    Elided(usize),
+}
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Call { name, pos: _, args, named } => {
+                let mut args = args.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+                args.extend(named.iter().map(|(id, x)| format!("{id}: {x}")));
+                write!(f, "{}({})", name.as_deref().unwrap_or("<function>"), args.join(", "))
+            }
+            Self::Repeated(n) => write!(f, "… repeated {n} times"),
+            Self::Elided(1) => write!(f, "… 1 tail call elided"),
+            Self::Elided(n) => write!(f, "… {n} tail calls elided"),
+        }
+    }
+}
+
+// the most frames a rendered traceback shows, half from each end
+pub const MAX_FRAMES: usize = 40;
+
+/// Texts that didn't come from a file, by the path their positions carry.
+pub type Sources = [(String, String)];
+
+fn source(pos: &Position, sources: &Sources) -> Option<String> {
+    if let Some((_, text)) = sources.iter().find(|(path, _)| path == &pos.0) { return Some(text.clone()) }
+    std::fs::read_to_string(&pos.0).ok()
+}
+// the closest char boundary at or before offset, a position that doesn't belong to the text can't split a character
+fn boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) { offset -= 1 }
+    offset
+}
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let offset = boundary(text, offset);
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    (text[..offset].matches('\n').count() + 1, text[line_start..offset].chars().count() + 1)
+}
+
+impl Error {
+    pub fn at(self, pos: Position) -> Self {
+        if self.pos().is_some() { return self }
+        Self::At(Box::new(self), pos)
+    }
+    /// Adds the frame of a call the error passed through, a run of calls from one site keeps only its first and last frame.
+    pub fn traced(self, frame: Frame) -> Self {
+        let (e, mut frames) = match self {
+            Self::Trace(e, frames) => (e, frames),
+            e => (Box::new(e), vec![])
+        };
+        if let Frame::Call { pos, .. } = &frame {
+            let site = |f: &Frame| matches!(f, Frame::Call { pos: p, .. } if p == pos);
+            match frames.as_mut_slice() {
+                [.., a, Frame::Repeated(n), b] if site(a) && site(b) => { *n += 1; frames.pop(); }
+                [.., a, b] if site(a) && site(b) => { frames.pop(); frames.push(Frame::Repeated(1)); }
+                _ => {}
+            }
+        }
+        frames.push(frame);
+        Self::Trace(e, frames)
+    }
+    /// Notes the calls in tail position an error passed through.
+    pub fn elided(self, n: usize) -> Self {
+        if n == 0 { return self }
+        self.traced(Frame::Elided(n))
+    }
+    pub fn pos(&self) -> Option<&Position> {
+        match self {
+            Self::At(_, pos) => Some(pos),
+            Self::Trace(e, _) => e.pos(),
+            _ => None
+        }
+    }
+    pub fn render(&self, path: &str, text: &str) -> String {
+        self.render_sources(&[(path.to_string(), text.to_string())])
+    }
+    /// Renders against several texts, like the inputs of a session whose functions were defined in earlier ones.
+    pub fn render_sources(&self, sources: &Sources) -> String {
+        if let Self::Many(errors) = self {
+            return errors.iter().map(|e| e.render_sources(sources)).collect::<Vec<String>>().join("\n")
+        }
+        if let Self::Trace(e, frames) = self {
+            let mut s = e.render_sources(sources);
+            s.push_str("\ntraceback (most recent call last):");
+            let omitted = frames.len().saturating_sub(MAX_FRAMES);
+            for (i, frame) in frames.iter().rev().enumerate() {
+                if omitted > 0 && i >= MAX_FRAMES / 2 && i < MAX_FRAMES / 2 + omitted {
+                    if i == MAX_FRAMES / 2 { s.push_str(&format!("\n  … {omitted} more frames")) }
+                    continue
+                }
+                let pos = match frame {
+                    Frame::Call { pos, .. } => pos,
+                    _ => { s.push_str(&format!("\n  {frame}")); continue }
+                };
+                match source(pos, sources) {
+                    Some(text) => {
+                        let (ln, col) = line_col(&text, pos.1.start);
+                        s.push_str(&format!("\n  in {frame} at {}:{ln}:{col}", pos.0));
+                    }
+                    None => s.push_str(&format!("\n  in {frame} at {}", pos.0))
+                }
+            }
+            return s
+        }
+        let pos = match self.pos() {
+            Some(pos) => pos,
+            None => return self.to_string()
+        };
+        let text = match source(pos, sources) {
+            Some(text) => text,
+            None => return format!("{self}\n --> {}", pos.0)
+        };
+        let start = boundary(&text, pos.1.start);
+        let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+        let line = &text[line_start..line_end];
+        let (ln, col) = line_col(&text, start);
+        let width = text[start..boundary(&text, pos.1.end.clamp(start, line_end))].chars().count().max(1);
+        let margin = " ".repeat(ln.to_string().len());
+        format!("{self}\n{margin}--> {}:{ln}:{col}\n{margin} |\n{ln} | {line}\n{margin} | {}{}",
+        pos.0, " ".repeat(col - 1), "^".repeat(width))
+    }
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Todo(s) => write!(f, "ERROR: todo - {s}"),
+            Self::Char(s) => write!(f, "ERROR: unexpected '{s}'"),
+            Self::UnexpectedToken(t) => write!(f, "ERROR: unexpected {}", t.name()),
+            Self::UnexpectedNode(n) => write!(f, "ERROR: unexpected {n}"),
+            Self::ExpectedToken(t) => write!(f, "ERROR: expected {}", t.name()),
+            Self::ExpectedType(t1, t2) => write!(f, "ERROR: expected type {t1}, got {t2}"),
+            Self::ExpectedTypes(ts, t2) => write!(f, "ERROR: expected type {}, got {t2}",
+            ts.iter().map(|x| x.to_string()).collect::<Vec<String>>().join("|")),
+            Self::ExpectedInt(v) => write!(f, "ERROR: expected an integer, got {v}"),
+            Self::InvalidBinaryOperation(t) => write!(f, "ERROR: invalid binary operation {}", t.name()),
+            Self::InvalidUnaryOperation(t) => write!(f, "ERROR: invalid unary operation {}", t.name()),
+            Self::IllegalBinaryOperation(t, v1, v2) => write!(f, "ERROR: illegal binary operation {} between {} and {}",
+            t.name(), v1.typ(), v2.typ()),
+            Self::IllegalUnaryOperation(t, v) => write!(f, "ERROR: illegal unary operation {} on {}",
+            t.name(), v.typ()),
+            Self::Immutable(s) => write!(f, "ERROR: {s} is immutable"),
+            Self::AlreadyDefined(s) => write!(f, "ERROR: {s} is already defined"),
+            Self::NotDefined(s) => write!(f, "ERROR: {s} is not defined"),
+            Self::Arity(min, max, recved) => match max {
+                Some(max) if max == min => write!(f, "ERROR: expected {min} arguments, got {recved}"),
+                Some(max) => write!(f, "ERROR: expected {min} to {max} arguments, got {recved}"),
+                None => write!(f, "ERROR: expected at least {min} arguments, got {recved}"),
+            }
+            Self::DuplicateArg(s) => write!(f, "ERROR: argument {s} is given more than once"),
+            Self::UnknownArg(s) => write!(f, "ERROR: {s} is not a parameter"),
+            Self::SkippedArg(s, skipped) => write!(f, "ERROR: argument {s} is given but {skipped} before it is not"),
+            Self::NoConvergence(method, iter) => write!(f, "ERROR: {method} did not converge after {iter} iterations"),
+            Self::NoSignChange(a, b) => write!(f, "ERROR: no sign change between {a} and {b}"),
+            Self::SubdivisionLimit(depth) => write!(f, "ERROR: integration exceeded subdivision depth {depth}"),
+            Self::Tolerance(tol) => write!(f, "ERROR: tolerance must be positive, got {tol}"),
+            Self::Import(path, e) => write!(f, "ERROR: could not import {path}: {e}"),
+            Self::ImportCycle(path) => write!(f, "ERROR: import cycle through {path}"),
+            Self::NoField(module, id) => write!(f, "ERROR: {module} has no field {id}"),
+            Self::At(e, _) => write!(f, "{e}"),
+            Self::Trace(e, frames) => write!(f, "{e}{}", frames.iter().rev().map(|x| format!("\n  in {x}")).collect::<String>()),
+            Self::Many(errors) => write!(f, "{}", errors.iter().map(|e| e.to_string()).collect::<Vec<String>>().join("\n")),
+            Self::UnknownCommand(cmd) => write!(f, "ERROR: unknown command {cmd}"),
+            Self::UnknownMode(mode) => write!(f, "ERROR: unknown mode {mode}, expected text, unicode, latex, mathml or json"),
+            Self::RecursionLimit(depth) => write!(f, "ERROR: maximum recursion depth of {depth} exceeded"),
+            Self::OutOfFuel(fuel) => write!(f, "ERROR: evaluation ran out of fuel after {fuel} steps"),
+            Self::Timeout(t) => write!(f, "ERROR: evaluation exceeded the time limit of {}ms", t.as_millis()),
+            Self::Json(e) => write!(f, "ERROR: json - {e}"),
+            Self::TooLarge(max) => write!(f, "ERROR: collection exceeds the maximum size of {max} elements"),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_are_clamped_to_char_boundaries() {
+        let e = Error::NotDefined("y".to_string()).at(Position("stdin".to_string(), 18..19));
+        assert_eq!(e.render("stdin", "f := x -> 0 + ∘∘ + y"), "ERROR: y is not defined\n --> stdin:1:16\n  |\n1 | f := x -> 0 + ∘∘ + y\n  |                ^");
+        let e = Error::NotDefined("y".to_string()).at(Position("stdin".to_string(), 40..41));
+        assert!(e.render("stdin", "∘∘").starts_with("ERROR: y is not defined\n --> stdin:1:3"));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::position::*;
+use crate::error::*;
+use crate::lexer::*;
+use crate::parser::*;
+
+// binding strength of each node, following the parser's precedence chain from assign down to atom
+pub fn precedence(node: &Node) -> usize {
+    match node {
+        Node::Block { .. } | Node::Assign { .. } | Node::Named { .. } => 0,
+        Node::Condition { .. } => 1,
+        Node::Function { .. } => 2,
+        Node::Binary { op, .. } => match op {
+            Token::Compose => 3,
+            Token::Equal | Token::NotEqual | Token::Less | Token::Greater | Token::LessEqual | Token::GreaterEqual => 4,
+            Token::Concat | Token::Remove => 5,
+            Token::Plus | Token::Minus | Token::PlusMinus => 6,
+            Token::Star | Token::Slash => 7,
+            Token::Exponent => 8,
+            _ => 13,
+        }
+        Node::Unary { op: Token::Minus, .. } => 9,
+        Node::Percent { .. } => 10,
+        Node::Unary { op: Token::Amount, .. } => 11,
+        Node::Call { .. } => 12,
+        _ => 14,
+    }
+}
+
+pub struct Formatter<'a> {
+    text: &'a str,
+}
+impl<'a> Formatter<'a> {
+    pub fn new(text: &'a str) -> Self { Self { text } }
+    // prints node, in parentheses if it binds looser than min
+    fn operand(&self, node: &Node, min: usize) -> String {
+        if precedence(node) < min { return format!("({})", self.node(node)) }
+        self.node(node)
+    }
+    fn list(&self, nodes: &[Node], sep: &str) -> String {
+        nodes.iter().map(|x| self.node(x)).collect::<Vec<String>>().join(sep)
+    }
+    pub fn node(&self, node: &Node) -> String {
+        match node {
+            // literals keep their spelling from the source
+            Node::Int { pos, .. } | Node::Float { pos, .. } => self.text[pos.1.clone()].to_string(),
+            Node::Word { v, .. } => v.clone(),
+            Node::Vector { v, .. } => format!("[{}]", self.list(v, " ")),
+            Node::Set { v, .. } => format!("{{{}}}", self.list(&v.values, " ")),
+            Node::Tuple { nodes, .. } => format!("({})", self.list(nodes, ", ")),
+            Node::Binary { op: Token::Field, left, right, .. } => format!("{}.{}", self.operand(left, 13), self.operand(right, 14)),
+            Node::Binary { op, left, right, .. } => {
+                let prec = precedence(node);
+                format!("{} {op} {}", self.operand(left, prec), self.operand(right, prec + 1))
+            }
+            // a minus in front of another one would lex as '--'
+            Node::Unary { op: Token::Minus, node, .. } => match node.as_ref() {
+                Node::Unary { op: Token::Minus, .. } => format!("-({})", self.node(node)),
+                _ => format!("-{}", self.operand(node, 9)),
+            }
+            Node::Unary { op: Token::Amount, node, .. } => format!("#{}", self.operand(node, 12)),
+            Node::Unary { op, node, .. } => format!("{op}{}", self.operand(node, 14)),
+            Node::Percent { node, .. } => format!("{}%", self.operand(node, 10)),
+            Node::Abs { node, .. } => format!("|{}|", self.node(node)),
+            Node::Call { v, args, partial, .. } => {
+                let mut args = args.iter().map(|x| self.node(x)).collect::<Vec<String>>();
+                if *partial { args.push("..".to_string()) }
+                format!("{}({})", self.operand(v, 12), args.join(", "))
+            }
+            Node::Assign { m, id, expr, .. } => format!("{} {} {}", self.node(id), if *m { ":=" } else { "::" }, self.operand(expr, 1)),
+            Node::Function { id, body, .. } => match id.as_ref() {
+                Node::Word { v, .. } => format!("({v}) -> {}", self.node(body)),
+                id => format!("{} -> {}", self.operand(id, 3), self.node(body)),
+            }
+            Node::Condition { cond, case, default, .. } => format!("{} ? {} : {}", self.operand(cond, 3), self.node(case), self.node(default)),
+            Node::Named { id, expr, .. } => format!("{id}: {}", self.node(expr)),
+            Node::Import { path, .. } => format!("import \"{path}\""),
+            Node::Block { nodes, .. } => self.list(nodes, "; "),
+            Node::Error { pos } => self.text[pos.1.clone()].to_string(),
+        }
+    }
+}
+
+// the comments between tokens as (offset, text, whether it starts its own line)
+fn comments(text: &str, poses: &[Position]) -> Vec<(usize, String, bool)> {
+    let mut comments = vec![];
+    let mut start = 0;
+    let ends = poses.iter().map(|x| x.1.start).chain([text.len()]);
+    for (end, next) in ends.zip(poses.iter().map(|x| x.1.end).chain([text.len()])) {
+        let mut offset = start;
+        while let Some(idx) = text[offset..end].find("//") {
+            let at = offset + idx;
+            let stop = text[at..end].find('\n').map_or(end, |i| at + i);
+            let own_line = text[text[..at].rfind('\n').map_or(0, |i| i + 1)..at].trim().is_empty();
+            comments.push((at, text[at..stop].trim_end().to_string(), own_line));
+            offset = stop;
+        }
+        start = next;
+    }
+    comments
+}
+fn blank_line(text: &str, from: usize, to: usize) -> bool {
+    from < to && text[from..to].matches('\n').count() > 1
+}
+
+/// Reprints a program with normalised spacing and parentheses, keeping its comments.
+pub fn format(text: &str, path: &str) -> Result<String, Error> {
+    if text.starts_with("#!") {
+        let end = text.find('\n').unwrap_or(text.len());
+        return Ok(format!("{}\n{}", &text[..end], format(&text[end..], path)?))
+    }
+    let (tokens, poses) = lex(text, path)?;
+    let comments = comments(text, &poses);
+    let nodes = if tokens.is_empty() { vec![] } else {
+        match parse(tokens, poses, path)? {
+            Node::Block { nodes, .. } => nodes,
+            node => vec![node],
+        }
+    };
+    let formatter = Formatter::new(text);
+    let mut out = String::new();
+    let mut comments = comments.into_iter().peekable();
+    let mut last: Option<usize> = None;
+    for (i, node) in nodes.iter().enumerate() {
+        let pos = node.pos();
+        // comments before and inside a statement go on their own lines above it
+        while let Some((at, comment, _)) = comments.next_if(|(at, _, _)| *at < pos.1.end) {
+            if last.is_some_and(|last| blank_line(text, last, at.min(pos.1.start))) { out.push('\n') }
+            out.push_str(&comment);
+            out.push('\n');
+            last = Some(at + comment.len());
+        }
+        if last.is_some_and(|last| blank_line(text, last, pos.1.start)) { out.push('\n') }
+        out.push_str(&formatter.node(node));
+        if i + 1 < nodes.len() { out.push(';') }
+        last = Some(pos.1.end);
+        let next = nodes.get(i + 1).map_or(text.len(), |x| x.pos().1.start);
+        if let Some((at, comment, _)) = comments.next_if(|(at, _, own_line)| !own_line && *at < next) {
+            out.push(' ');
+            out.push_str(&comment);
+            last = Some(at + comment.len());
+        }
+        out.push('\n');
+    }
+    for (at, comment, _) in comments {
+        if last.is_some_and(|last| blank_line(text, last, at)) { out.push('\n') }
+        out.push_str(&comment);
+        out.push('\n');
+        last = Some(at + comment.len());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statements_are_separated() {
+        assert_eq!(format("a := 1; -a", "test").unwrap(), "a := 1;\n-a\n");
+        assert_eq!(format("a := 1;\nb := 2;\n", "test").unwrap(), "a := 1;\nb := 2\n");
+    }
+    const SOURCES: &[&str] = &[
+        "1+2*3", "(1 + 2) * 3", "2 ^ 3 ^ 2", "(2 ^ 3) ^ 2", "1 - (2 - 3)", "-(-x)", "-x^2", "(-x)^2",
+        "f := x -> x + 1; f(2)", "g :: (a, b := 2, ..r) -> a * b", "h := x -> y -> x + y", "(x -> x)(1)",
+        "a < b ? a : b", "a ? b : c ? d : e", "(a ? b : c) ? d : e", "f := n -> n = 0 ? 1 : n * f(n - 1)",
+        "[1 2 [3 4]] ++ [5]", "{1 2} -- {2}", "(1, 2, 3)", "#[1 2 3]", "50%", "|x - 3|", "m.f(1)",
+        "(f ∘ g)(1)", "f(1, ..)", "f(x: 1, 2)", "f(x := 1, 2)", "..r", "import \"lib.eq\"", "x := (y := 2) + 1",
+        "a := 1; // one\n\n// two\nb := 2; c := 3 // three\n// end",
+        "#!/usr/bin/env equa\nx := 1;\nx + 1",
+    ];
+    fn tree(text: &str) -> String {
+        let text = text.strip_prefix("#!").map_or(text, |t| &t[t.find('\n').unwrap()..]);
+        let (tokens, poses) = lex(text, "test").unwrap();
+        parse(tokens, poses, "test").unwrap().to_string()
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        for text in SOURCES {
+            let once = format(text, "test").unwrap();
+            assert_eq!(format(&once, "test").unwrap(), once, "{text}");
+        }
+    }
+    #[test]
+    fn formatting_keeps_the_meaning() {
+        for text in SOURCES {
+            assert_eq!(tree(&format(text, "test").unwrap()), tree(text), "{text}");
+        }
+    }
+    #[test]
+    fn parentheses_and_comments() {
+        assert_eq!(format("1+2*3", "test").unwrap(), "1 + 2 * 3\n");
+        assert_eq!(format("(((1 + 2))) * 3", "test").unwrap(), "(1 + 2) * 3\n");
+        assert_eq!(format("-(-x)", "test").unwrap(), "-(-x)\n");
+        assert_eq!(format("a := 1; // one\n\n// two\nb := 2", "test").unwrap(), "a := 1; // one\n\n// two\nb := 2\n");
+    }
+}
@@ -1,203 +1,828 @@
-use crate::set::*;
-use crate::position::*;
-use crate::error::*;
-use crate::lexer::*;
-use crate::parser::*;
-use crate::value::*;
-use std::collections::HashMap;
-
-#[derive(Debug, Clone)]
-pub struct Scope {
-    vars: HashMap<String, Value>,
-    consts: HashMap<String, Value>
-}
-impl Scope {
-    pub fn new() -> Self { Self { vars: HashMap::new(), consts: HashMap::new() } }
-    pub fn get(&self, id: &String) -> Option<&Value> {
-        self.vars.get(id).or_else(|| self.consts.get(id))
-    }
-    pub fn is_const(&self, id: &String) -> bool {
-        self.consts.get(id).is_some()
-    }
-    pub fn set(&mut self, id: &String, value: &Value) -> Option<Value> {
-        self.vars.insert(id.clone(), value.clone())
-    }
-    pub fn set_const(&mut self, id: &String, value: &Value) -> Option<Value> {
-        self.consts.insert(id.clone(), value.clone())
-    }
-}
-
-#[derive(Debug)]
-pub struct Context {
-    scopes: Vec<Scope>,
-    global: Scope,
-}
-impl Context {
-    pub fn new() -> Self { Self { scopes: vec![Scope::new()], global: Scope::new() } }
-    pub fn get(&self, id: &String) -> Option<&Value> {
-        for scope in self.scopes.iter().rev() {
-            let v = scope.get(id);
-            if v.is_some() { return v }
-        }
-        self.global.get(id)
-    }
-    pub fn set(&mut self, id: &String, value: &Value) -> Result<(), ()> {
-        if self.global.get(id).is_some() { return Err(()) }
-        for scope in self.scopes.iter_mut() {
-            if scope.get(id).is_some() {
-                if scope.is_const(id) { return Err(()) }
-                let v = scope.set(id, value);
-                return Ok(())
-            }
-        }
-        self.scopes.last_mut().unwrap().set(id, value);
-        Ok(())
-    }
-    pub fn set_const(&mut self, id: &String, value: &Value) -> Result<(), ()> {
-        if self.global.get(id).is_some() { return Err(()) }
-        for scope in self.scopes.iter() {
-            if scope.get(id).is_some() { return Err(()) }
-        }
-        self.scopes.last_mut().unwrap().set_const(id, value);
-        Ok(())
-    }
-    pub fn global(&mut self, id: &String, value: &Value) -> Result<(), ()> {
-        if self.global.get(id).is_some() { return Err(()) }
-        for scope in self.scopes.iter() {
-            if scope.get(id).is_some() { return Err(()) }
-        }
-        self.global.set(id, value);
-        Ok(())
-    }
-    pub fn global_const(&mut self, id: &String, value: &Value) -> Result<(), ()> {
-        if self.global.get(id).is_some() { return Err(()) }
-        for scope in self.scopes.iter() {
-            if scope.get(id).is_some() { return Err(()) }
-        }
-        self.global.set_const(id, value);
-        Ok(())
-    }
-}
-
-pub fn get(node: &Node, context: &mut Context, path: &str) -> Result<Value, Error> {
-    match node {
-        Node::Int { v, pos } => Ok(Value::Number(Number::Int(*v))),
-        Node::Float { v, pos } => Ok(Value::Number(Number::Float(*v))),
-        Node::Percent { node, pos } => {
-            let value = get(node, context, path)?;
-            if let Value::Number(v) = value {
-                return Ok(Value::Number(v / Number::Int(100)))
-            }
-            Err(Error::ExpectedType(Type::Number, value.typ()))
-        }
-        Node::Abs { node, pos } => {
-            let value = get(node, context, path)?;
-            if let Value::Number(v) = value {
-                return Ok(Value::Number(if v < Number::Int(0) { -v } else { v }))
-            }
-            Err(Error::ExpectedTypes(vec![Type::Number, Type::Set], value.typ()))
-        }
-        Node::Word { v, pos } => {
-            let value = context.get(v);
-            if value.is_none() { return Err(Error::NotDefined(v.clone())) }
-            Ok(value.unwrap().clone())
-        }
-        Node::Vector { v, pos } => {
-            let mut values: Vec<Value> = vec![];
-            for n in v.iter() {
-                let value = get(n, context, path)?;
-                values.push(value);
-            }
-            Ok(Value::Vector(values))
-        }
-        Node::Set { v, pos } => {
-            let mut values: Set<Value> = Set::new();
-            for n in v.values.iter() {
-                let value = get(n, context, path)?;
-                values.add(value);
-            }
-            Ok(Value::Set(values))
-        }
-        Node::Binary { op, left, right, pos } => {
-            let v1 = get(left.as_ref(), context, path)?;
-            let v2 = get(right.as_ref(), context, path)?;
-            let value = v1.binop(op, &v2);
-            if value.is_none() { return Err(Error::IllegalBinaryOperation(op.clone(), v1, v2)) }
-            Ok(value.unwrap())
-        }
-        Node::Unary { op, node, pos } => {
-            let v = get(node.as_ref(), context, path)?;
-            let value = v.unop(op);
-            if value.is_none() { return Err(Error::IllegalUnaryOperation(op.clone(), v)) }
-            Ok(value.unwrap())
-        }
-        Node::Call { v, args, pos } => {
-            let func = get(v, context, path)?;
-            if let Value::Function(params, body) = func {
-                let global = context.global.clone();
-                let mut fcontext = Context::new(); fcontext.global = global;
-                for i in 0..params.len() {
-                    if let Some(n) = args.get(i) {
-                        let value = get(n, context, path)?;
-                        fcontext.set(&params[i], &value);
-                    } else {
-                        return Err(Error::TooFewArgs(params.len(), args.len()))
-                    }
-                }
-                return get(&body, &mut fcontext, path)
-            }
-            if let Value::ForeignFunction(params, function) = func {
-                let global = context.global.clone();
-                let mut fcontext = Context::new(); fcontext.global = global;
-                for i in 0..params.len() {
-                    if let Some(n) = args.get(i) {
-                        let value = get(n, context, path)?;
-                        fcontext.set(&params[i], &value);
-                    } else {
-                        return Err(Error::TooFewArgs(params.len(), args.len()))
-                    }
-                }
-                return function(&mut fcontext)
-            }
-            Err(Error::ExpectedType(Type::Function, func.typ()))
-        }
-        Node::Tuple { nodes, pos } => {
-            let mut values: Vec<Value> = vec![];
-            for n in nodes.iter() {
-                let value = get(n, context, path)?;
-                values.push(value);
-            }
-            Ok(Value::Tuple(values))
-        }
-        Node::Function { id, body, pos } => {
-            if let Node::Word { v, pos } = id.as_ref() {
-                return Ok(Value::Function(vec![v.clone()], body.as_ref().clone()))
-            } else if let Node::Tuple { nodes, pos } = id.as_ref() {
-                let mut params: Vec<String> = vec![];
-                for n in nodes.iter() {
-                    if let Node::Word { v, pos } = n {
-                        params.push(v.clone());
-                    } else {
-                        return Err(Error::UnexpectedNode(n.clone()))
-                    }
-                }
-                return Ok(Value::Function(params, body.as_ref().clone()))
-            }
-            Err(Error::UnexpectedNode(id.as_ref().clone()))
-        }
-        Node::Assign { m, id, expr, pos } => {
-            let value = get(expr.as_ref(), context, path)?;
-            if let Node::Word { v, pos } = id.as_ref() {
-                if *m {
-                    let res = context.set(v, &value);
-                    if res.is_err() { return Err(Error::Immutable(v.clone())) }
-                } else {
-                    let res = context.set_const(v, &value);
-                    if res.is_err() { return Err(Error::AlreadyDefined(v.clone())) }
-                }
-                return Ok(value)
-            }
-            Err(Error::UnexpectedNode(id.as_ref().clone()))
-        }
-    }
-}
\ No newline at end of file
+use crate::set::*;
+use crate::error::*;
+use crate::lexer::*;
+use crate::parser::*;
+use crate::value::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    vars: HashMap<String, Value>,
+    consts: HashMap<String, Value>
+}
+impl Scope {
+    pub fn new() -> Self { Self { vars: HashMap::new(), consts: HashMap::new() } }
+}
+impl Default for Scope {
+    fn default() -> Self { Self::new() }
+}
+impl Scope {
+    pub fn get(&self, id: &String) -> Option<&Value> {
+        self.vars.get(id).or_else(|| self.consts.get(id))
+    }
+    pub fn is_const(&self, id: &String) -> bool {
+        self.consts.contains_key(id)
+    }
+    pub fn set(&mut self, id: &str, value: &Value) -> Option<Value> {
+        self.vars.insert(id.to_string(), value.clone())
+    }
+    pub fn set_const(&mut self, id: &str, value: &Value) -> Option<Value> {
+        self.consts.insert(id.to_string(), value.clone())
+    }
+    pub fn bindings(&self) -> Vec<(&String, &Value, bool)> {
+        let mut bindings: Vec<(&String, &Value, bool)> = self.vars.iter().map(|(id, v)| (id, v, false))
+        .chain(self.consts.iter().map(|(id, v)| (id, v, true))).collect();
+        bindings.sort_by(|a, b| a.0.cmp(b.0));
+        bindings
+    }
+}
+
+pub const MAX_DEPTH: usize = 10000;
+// evaluation recurses on the native stack, which is moved onto the heap whenever less than RED_ZONE of it is left,
+// so the depth limits hold on any thread an embedder runs us on
+const RED_ZONE: usize = 256 * 1024;
+const STACK_GROWTH: usize = 4 * 1024 * 1024;
+pub fn grow<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, STACK_GROWTH, f)
+}
+// how many steps pass between checks of the deadline
+const CLOCK_INTERVAL: u64 = 256;
+/// The most results a memoized function keeps, later ones are computed every time. Each counts against `max_size`.
+pub const MEMO_CAPACITY: usize = 1 << 16;
+
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub fuel: Option<u64>,
+    pub max_depth: usize,
+    pub max_size: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+impl Default for Limits {
+    fn default() -> Self { Self { fuel: None, max_depth: MAX_DEPTH, max_size: None, timeout: None } }
+}
+
+// scopes are shared between a caller and its frames and only copied when one of them writes to a shared scope
+#[derive(Debug)]
+pub struct Context {
+    scopes: Vec<Rc<Scope>>,
+    answers: Rc<Scope>,
+    global: Rc<Scope>,
+    depth: usize,
+    pub limits: Limits,
+    // shared by every frame of an evaluation, so calls can't reset them
+    steps: Rc<Cell<u64>>,
+    deadline: Option<Instant>,
+    imports: Rc<Vec<String>>,
+    base: Option<Rc<Path>>,
+}
+impl Context {
+    pub fn new() -> Self { Self { scopes: vec![Rc::new(Scope::new())], answers: Rc::new(Scope::new()), global: Rc::new(Scope::new()), depth: 0,
+    limits: Limits::default(), steps: Rc::new(Cell::new(0)), deadline: None, imports: Rc::new(vec![]), base: None } }
+}
+impl Default for Context {
+    fn default() -> Self { Self::new() }
+}
+impl Context {
+    pub fn get(&self, id: &String) -> Option<&Value> {
+        for scope in self.scopes.iter().rev() {
+            let v = scope.get(id);
+            if v.is_some() { return v }
+        }
+        self.answers.get(id).or_else(|| self.global.get(id))
+    }
+    pub fn scopes(&self) -> &Vec<Rc<Scope>> { &self.scopes }
+    pub fn global_scope(&self) -> &Scope { &self.global }
+    pub fn answers(&self) -> &Scope { &self.answers }
+    pub fn answer(&mut self, value: &Value) -> usize {
+        let n = self.answers.bindings().iter().filter(|(id, _, _)| id.as_str() != "ans").count() + 1;
+        let answers = Rc::make_mut(&mut self.answers);
+        answers.set(&format!("ans{n}"), value);
+        answers.set("ans", value);
+        n
+    }
+    /// Starts counting steps and time against the limits, unless an evaluation is already running.
+    pub fn start(&mut self) {
+        if self.depth > 0 { return }
+        self.steps.set(0);
+        self.deadline = self.limits.timeout.map(|t| Instant::now() + t);
+    }
+    pub fn steps(&self) -> u64 { self.steps.get() }
+    pub fn depth(&self) -> usize { self.depth }
+    pub fn set_depth(&mut self, depth: usize) { self.depth = depth }
+    pub fn step(&self) -> Result<(), Error> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if let Some(fuel) = self.limits.fuel {
+            if steps > fuel { return Err(Error::OutOfFuel(fuel)) }
+        }
+        if steps.is_multiple_of(CLOCK_INTERVAL) { self.check_time()? }
+        Ok(())
+    }
+    /// Fails once the time of the running evaluation is up, parsing is checked with this before anything runs.
+    pub fn check_time(&self) -> Result<(), Error> {
+        if let (Some(deadline), Some(timeout)) = (self.deadline, self.limits.timeout) {
+            if Instant::now() >= deadline { return Err(Error::Timeout(timeout)) }
+        }
+        Ok(())
+    }
+    pub fn check_size(&self, value: &Value) -> Result<(), Error> {
+        if let Some(max) = self.limits.max_size {
+            if size(value, max) > max { return Err(Error::TooLarge(max)) }
+        }
+        Ok(())
+    }
+    pub fn reset(&mut self) {
+        self.scopes = vec![Rc::new(Scope::new())];
+    }
+    pub fn local(&self, id: &String) -> Option<&Value> {
+        self.scopes.last().unwrap().get(id)
+    }
+    /// Whether `id` is bound in a scope or globally, and if so whether it can't be reassigned.
+    pub fn binding(&self, id: &String) -> Option<bool> {
+        if self.global.get(id).is_some() { return Some(true) }
+        self.scopes.iter().find(|scope| scope.get(id).is_some()).map(|scope| scope.is_const(id))
+    }
+    pub fn define(&mut self, id: &str, value: &Value) {
+        Rc::make_mut(self.scopes.last_mut().unwrap()).set(id, value);
+    }
+    /// The context a function body runs in: the top-level definitions and a fresh scope for its parameters and locals.
+    /// Bodies see the top level so functions can call themselves and each other, but never the locals of their caller,
+    /// and what they assign stays in their own copy.
+    pub fn frame(&self) -> Self {
+        Self { scopes: vec![self.scopes[0].clone(), Rc::new(Scope::new())], answers: self.answers.clone(), global: self.global.clone(), depth: self.depth, limits: self.limits.clone(),
+        steps: self.steps.clone(), deadline: self.deadline, imports: self.imports.clone(), base: self.base.clone() }
+    }
+    /// A copy to evaluate in whose definitions and steps are thrown away with it, the scopes are only copied if it writes to them.
+    pub fn scratch(&self) -> Self {
+        Self { scopes: self.scopes.clone(), answers: self.answers.clone(), global: self.global.clone(), depth: self.depth, limits: self.limits.clone(),
+        steps: Rc::new(Cell::new(0)), deadline: self.deadline, imports: self.imports.clone(), base: self.base.clone() }
+    }
+    pub fn module(&self, scope: &Rc<Scope>) -> Self {
+        Self { scopes: vec![scope.clone()], answers: self.answers.clone(), global: self.global.clone(), depth: self.depth, limits: self.limits.clone(),
+        steps: self.steps.clone(), deadline: self.deadline, imports: self.imports.clone(), base: self.base.clone() }
+    }
+    /// Sets the directory that relative imports in code not read from a file are resolved from, without one they fail.
+    pub fn set_base(&mut self, dir: &Path) { self.base = Some(dir.into()) }
+    pub fn set(&mut self, id: &String, value: &Value) -> Result<(), Error> {
+        if self.global.get(id).is_some() { return Err(Error::Immutable(id.clone())) }
+        for scope in self.scopes.iter_mut() {
+            if scope.get(id).is_some() {
+                if scope.is_const(id) { return Err(Error::Immutable(id.clone())) }
+                Rc::make_mut(scope).set(id, value);
+                return Ok(())
+            }
+        }
+        Rc::make_mut(self.scopes.last_mut().unwrap()).set(id, value);
+        Ok(())
+    }
+    pub fn set_const(&mut self, id: &String, value: &Value) -> Result<(), Error> {
+        if self.binding(id).is_some() { return Err(Error::AlreadyDefined(id.clone())) }
+        Rc::make_mut(self.scopes.last_mut().unwrap()).set_const(id, value);
+        Ok(())
+    }
+    pub fn global(&mut self, id: &String, value: &Value) -> Result<(), Error> {
+        if self.binding(id).is_some() { return Err(Error::AlreadyDefined(id.clone())) }
+        Rc::make_mut(&mut self.global).set(id, value);
+        Ok(())
+    }
+    pub fn global_const(&mut self, id: &String, value: &Value) -> Result<(), Error> {
+        if self.binding(id).is_some() { return Err(Error::AlreadyDefined(id.clone())) }
+        Rc::make_mut(&mut self.global).set_const(id, value);
+        Ok(())
+    }
+}
+
+// counts the elements of nested collections, stopping once it passes max
+fn size(value: &Value, max: usize) -> usize {
+    let values = match value {
+        Value::Vector(values) | Value::Tuple(values) => values,
+        Value::Set(values) => &values.values,
+        _ => return 0
+    };
+    let mut n = values.len();
+    for v in values {
+        if n > max { break }
+        n += size(v, max - n);
+    }
+    n
+}
+
+pub fn get(node: &Node, context: &mut Context, path: &str) -> Result<Value, Error> {
+    if context.depth >= context.limits.max_depth { return Err(Error::RecursionLimit(context.limits.max_depth).at(node.pos())) }
+    context.step().map_err(|e| e.at(node.pos()))?;
+    context.depth += 1;
+    let value = grow(|| eval(node, context, path)).and_then(|v| context.check_size(&v).map(|_| v)).map_err(|e| e.at(node.pos()));
+    context.depth -= 1;
+    value
+}
+fn eval(node: &Node, context: &mut Context, path: &str) -> Result<Value, Error> {
+    match node {
+        Node::Int { v, pos: _ } => Ok(Value::Number(Number::Int(*v))),
+        Node::Float { v, pos: _ } => Ok(Value::Number(Number::Float(*v))),
+        Node::Percent { node, pos: _ } => {
+            let value = get(node, context, path)?;
+            if let Value::Number(v) = value {
+                return Ok(Value::Number(v / Number::Int(100)))
+            }
+            Err(Error::ExpectedType(Type::Number, value.typ()))
+        }
+        Node::Abs { node, pos: _ } => {
+            let value = get(node, context, path)?;
+            if let Value::Number(v) = value {
+                return Ok(Value::Number(if v < Number::Int(0) { -v } else { v }))
+            }
+            Err(Error::ExpectedTypes(vec![Type::Number, Type::Set], value.typ()))
+        }
+        Node::Word { v, pos: _ } => {
+            let value = context.get(v);
+            if value.is_none() { return Err(Error::NotDefined(v.clone())) }
+            Ok(value.unwrap().clone())
+        }
+        Node::Vector { v, pos: _ } => {
+            let mut values: Vec<Value> = vec![];
+            for n in v.iter() {
+                let value = get(n, context, path)?;
+                values.push(value);
+            }
+            Ok(Value::Vector(values.into()))
+        }
+        Node::Set { v, pos: _ } => {
+            let mut values: Set<Value> = Set::new();
+            for n in v.values.iter() {
+                let value = get(n, context, path)?;
+                values.add(value);
+            }
+            Ok(Value::Set(values.into()))
+        }
+        Node::Binary { op: Token::Field, left, right: word, pos: _ } => {
+            let v1 = get(left.as_ref(), context, path)?;
+            if let (Value::Module(module, scope), Node::Word { v, pos: _ }) = (&v1, word.as_ref()) {
+                return match scope.get(v) {
+                    Some(value) if value.callable() => Ok(Value::Scoped(Rc::new(value.clone()), scope.clone())),
+                    Some(value) => Ok(value.clone()),
+                    None => Err(Error::NoField(module.clone(), v.clone()))
+                }
+            }
+            let v2 = get(word.as_ref(), context, path)?;
+            let value = v1.binop(&Token::Field, &v2);
+            if value.is_none() { return Err(Error::IllegalBinaryOperation(Token::Field, v1, v2)) }
+            Ok(value.unwrap())
+        }
+        Node::Binary { op, left, right, pos: _ } => {
+            let v1 = get(left.as_ref(), context, path)?;
+            let v2 = get(right.as_ref(), context, path)?;
+            let value = v1.binop(op, &v2);
+            if value.is_none() { return Err(Error::IllegalBinaryOperation(op.clone(), v1, v2)) }
+            Ok(value.unwrap())
+        }
+        Node::Unary { op, node, pos: _ } => {
+            let v = get(node.as_ref(), context, path)?;
+            let value = v.unop(op);
+            if value.is_none() { return Err(Error::IllegalUnaryOperation(op.clone(), v)) }
+            Ok(value.unwrap())
+        }
+        Node::Call { v, args, partial, pos } => {
+            let func = get(v, context, path)?;
+            let (values, named) = arguments(args, context, path)?;
+            if *partial {
+                if !func.callable() { return Err(Error::ExpectedType(Type::Function, func.typ())) }
+                return Ok(Value::Partial(Rc::new(func), values.into(), named.into()))
+            }
+            // the arguments are only lent to the call, so the frame of an error is built from them without copying
+            call_with(&func, &values, &named, context, path).map_err(|e| e.at(pos.clone()).traced(Frame::Call {
+                name: match v.as_ref() {
+                    Node::Word { v, pos: _ } => Some(v.clone()),
+                    Node::Binary { op: Token::Field, left, right, pos: _ } => Some(format!("{left}.{right}")),
+                    _ => None
+                },
+                pos: pos.clone(), args: values, named
+            }))
+        }
+        Node::Tuple { nodes, pos: _ } => {
+            let mut values: Vec<Value> = vec![];
+            for n in nodes.iter() {
+                let value = get(n, context, path)?;
+                values.push(value);
+            }
+            Ok(Value::Tuple(values.into()))
+        }
+        Node::Function { id, body, pos: _ } => {
+            let mut params: Vec<Param> = vec![];
+            if let Node::Tuple { nodes, pos: _ } = id.as_ref() {
+                for n in nodes.iter() {
+                    if params.last().is_some_and(|p| p.rest) { return Err(Error::UnexpectedNode(n.clone())) }
+                    params.push(param(n)?);
+                }
+            } else {
+                params.push(param(id)?);
+            }
+            Ok(Value::Function(params.into(), body.clone()))
+        }
+        Node::Condition { cond, case, default, pos: _ } => {
+            if get(cond, context, path)?.truthy() {
+                get(case, context, path)
+            } else {
+                get(default, context, path)
+            }
+        }
+        Node::Named { .. } | Node::Error { .. } => Err(Error::UnexpectedNode(node.clone())),
+        Node::Block { nodes, pos: _ } => {
+            let mut value = Value::Tuple(vec![].into());
+            for n in nodes.iter() {
+                value = get(n, context, path)?;
+            }
+            Ok(value)
+        }
+        Node::Import { path: file, pos } => {
+            // relative to the importing file, or to the base directory for code that isn't in one
+            let from = Path::new(&pos.0);
+            let file = if from.is_file() {
+                from.parent().unwrap().join(file)
+            } else if Path::new(file).is_absolute() {
+                PathBuf::from(file)
+            } else if let Some(base) = &context.base {
+                base.join(file)
+            } else {
+                return Err(Error::Import(file.clone(), format!("{} is not a file and no base directory is set", pos.0)))
+            };
+            let name = file.to_string_lossy().to_string();
+            let id = match file.canonicalize() {
+                Ok(id) => id.to_string_lossy().to_string(),
+                Err(e) => return Err(Error::Import(name, e.to_string()))
+            };
+            if context.imports.contains(&id) { return Err(Error::ImportCycle(name)) }
+            let text = match std::fs::read_to_string(&file) {
+                Ok(text) => text,
+                Err(e) => return Err(Error::Import(name, e.to_string()))
+            };
+            let mut mcontext = context.module(&Rc::new(Scope::new()));
+            Rc::make_mut(&mut mcontext.imports).push(id);
+            crate::run(&text, &mut mcontext, &name)?;
+            Ok(Value::Module(name, mcontext.scopes.remove(0)))
+        }
+        Node::Assign { m, id, expr, pos: _ } => {
+            let value = get(expr.as_ref(), context, path)?;
+            if let Node::Word { v, pos: _ } = id.as_ref() {
+                if *m { context.set(v, &value)? } else { context.set_const(v, &value)? }
+                return Ok(value)
+            }
+            Err(Error::UnexpectedNode(id.as_ref().clone()))
+        }
+    }
+}
+/// Arguments passed by name, in the order they were given.
+pub type Named = Vec<(String, Value)>;
+
+pub fn arguments(args: &[Node], context: &mut Context, path: &str) -> Result<(Vec<Value>, Named), Error> {
+    let mut values: Vec<Value> = vec![];
+    let mut named: Named = vec![];
+    for n in args.iter() {
+        if let Node::Named { id, expr, pos: _ } = n {
+            let value = get(expr, context, path)?;
+            named.push((id.clone(), value));
+            continue
+        }
+        let value = get(n, context, path)?;
+        values.push(value);
+    }
+    Ok((values, named))
+}
+
+pub enum Tail { Value(Value), Call(Value, Vec<Value>, Named) }
+pub fn tail(node: &Node, context: &mut Context, path: &str) -> Result<Tail, Error> {
+    match node {
+        Node::Call { v, args, partial: false, pos: _ } => {
+            let func = get(v, context, path)?;
+            let (values, named) = arguments(args, context, path)?;
+            Ok(Tail::Call(func, values, named))
+        }
+        Node::Condition { cond, case, default, pos: _ } => {
+            if get(cond, context, path)?.truthy() {
+                tail(case, context, path)
+            } else {
+                tail(default, context, path)
+            }
+        }
+        _ => Ok(Tail::Value(get(node, context, path)?))
+    }
+}
+
+pub fn param(node: &Node) -> Result<Param, Error> {
+    match node {
+        Node::Word { v, pos: _ } => return Ok(Param::new(v)),
+        Node::Assign { m: true, id, expr, pos: _ } => if let Node::Word { v, pos: _ } = id.as_ref() {
+            return Ok(Param { default: Some(expr.as_ref().clone()), ..Param::new(v) })
+        }
+        Node::Unary { op: Token::Range, node, pos: _ } => if let Node::Word { v, pos: _ } = node.as_ref() {
+            return Ok(Param { rest: true, ..Param::new(v) })
+        }
+        _ => {}
+    }
+    Err(Error::UnexpectedNode(node.clone()))
+}
+
+// every name has to be a parameter that isn't given twice, checked before the count so a typo isn't reported as an arity error
+pub fn check_named(params: &[Param], named: &[(String, Value)]) -> Result<(), Error> {
+    for (i, (id, _)) in named.iter().enumerate() {
+        if !params.iter().any(|p| !p.rest && &p.id == id) { return Err(Error::UnknownArg(id.clone())) }
+        if named[..i].iter().any(|(x, _)| x == id) { return Err(Error::DuplicateArg(id.clone())) }
+    }
+    Ok(())
+}
+
+pub fn bind(params: &[Param], args: &[Value], named: &[(String, Value)], fcontext: &mut Context, path: &str) -> Result<(), Error> {
+    check_named(params, named)?;
+    let given = args.len() + named.len();
+    let min = params.iter().filter(|p| p.default.is_none() && !p.rest).count();
+    let max = if params.iter().any(|p| p.rest) { None } else { Some(params.len()) };
+    let mut positional = args.iter();
+    for param in params.iter() {
+        if param.rest {
+            fcontext.define(&param.id, &Value::Tuple(positional.by_ref().cloned().collect::<Vec<Value>>().into()));
+            continue
+        }
+        let value = if let Some(value) = positional.next() {
+            if named.iter().any(|(id, _)| id == &param.id) { return Err(Error::DuplicateArg(param.id.clone())) }
+            value.clone()
+        } else if let Some((_, value)) = named.iter().find(|(id, _)| id == &param.id) {
+            value.clone()
+        } else if let Some(node) = &param.default {
+            get(node, fcontext, path)?
+        } else {
+            return Err(Error::Arity(min, max, given))
+        };
+        fcontext.define(&param.id, &value);
+    }
+    if positional.next().is_some() { return Err(Error::Arity(min, max, given)) }
+    Ok(())
+}
+
+pub fn call(func: &Value, args: Vec<Value>, context: &mut Context, path: &str) -> Result<Value, Error> {
+    call_with(func, &args, &[], context, path)
+}
+pub fn call_with(func: &Value, args: &[Value], named: &[(String, Value)], context: &mut Context, path: &str) -> Result<Value, Error> {
+    grow(|| apply(func, args, named, context, path))
+}
+fn apply(func: &Value, args: &[Value], named: &[(String, Value)], context: &mut Context, path: &str) -> Result<Value, Error> {
+    let given = args.len() + named.len();
+    if let Value::Function(params, body) = func {
+        let mut fcontext = context.frame();
+        bind(params, args, named, &mut fcontext, path)?;
+        let mut body = body.clone();
+        // calls in tail position reuse this loop instead of growing the stack, a trace only counts them
+        let mut elided = 0;
+        loop {
+            match tail(&body, &mut fcontext, path).map_err(|e| e.elided(elided))? {
+                Tail::Value(value) => return Ok(value),
+                Tail::Call(Value::Function(params, next), args, named) => {
+                    elided += 1;
+                    fcontext = context.frame();
+                    bind(&params, &args, &named, &mut fcontext, path).map_err(|e| e.elided(elided))?;
+                    body = next;
+                }
+                Tail::Call(func, args, named) => return call_with(&func, &args, &named, context, path).map_err(|e| e.elided(elided + 1)),
+            }
+        }
+    }
+    if let Value::ForeignFunction(params, function) = func {
+        check_named(params, named)?;
+        let min = params.iter().filter(|p| !p.optional).count();
+        let max = Some(params.len());
+        if named.is_empty() && args.len() >= min && args.len() <= params.len() { return function(args, context, path) }
+        let mut positional = args.iter();
+        let mut values: Vec<Value> = vec![];
+        // natives take their arguments by position, so an optional one can only be left out if all after it are too
+        let mut skipped: Option<&String> = None;
+        for param in params.iter() {
+            let value = if let Some(value) = positional.next() {
+                if named.iter().any(|(name, _)| name == &param.id) { return Err(Error::DuplicateArg(param.id.clone())) }
+                value.clone()
+            } else if let Some((_, value)) = named.iter().find(|(name, _)| name == &param.id) {
+                if let Some(skipped) = skipped { return Err(Error::SkippedArg(param.id.clone(), skipped.clone())) }
+                value.clone()
+            } else if !param.optional {
+                return Err(Error::Arity(min, max, given))
+            } else {
+                skipped = skipped.or(Some(&param.id));
+                continue
+            };
+            values.push(value);
+        }
+        if positional.next().is_some() { return Err(Error::Arity(min, max, given)) }
+        return function(&values, context, path)
+    }
+    if let Value::Compiled(proto) = func {
+        return crate::vm::call(proto, args, named, context)
+    }
+    if let Value::Memo(func, cache) = func {
+        let keys = args.iter().map(|x| x.key()).collect::<Option<Vec<Key>>>();
+        if let (Some(keys), true) = (keys, named.is_empty()) {
+            if let Some(value) = cache.borrow().get(&keys) { return Ok(value.clone()) }
+            let value = call_with(func, args, &[], context, path)?;
+            let len = cache.borrow().len();
+            if context.limits.max_size.is_some_and(|max| len >= max) { return Err(Error::TooLarge(context.limits.max_size.unwrap())) }
+            if len < MEMO_CAPACITY { cache.borrow_mut().insert(keys, value.clone()); }
+            return Ok(value)
+        }
+        return call_with(func, args, named, context, path)
+    }
+    if let Value::Scoped(func, scope) = func {
+        let mut mcontext = context.module(scope);
+        return call_with(func, args, named, &mut mcontext, path)
+    }
+    if let Value::Composed(func, other) = func {
+        let value = call_with(other, args, named, context, path)?;
+        return call_with(func, &[value], &[], context, path)
+    }
+    if let Value::Partial(func, bound, bound_named) = func {
+        let mut values = bound.to_vec();
+        values.extend_from_slice(args);
+        let mut named = named.to_vec();
+        for (id, value) in bound_named.iter() {
+            if !named.iter().any(|(name, _)| name == id) { named.push((id.clone(), value.clone())) }
+        }
+        return call_with(func, &values, &named, context, path)
+    }
+    Err(Error::ExpectedType(Type::Function, func.typ()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    fn eval(text: &str) -> Result<Value, Error> {
+        Engine::new().eval(text).map(|v| v.unwrap())
+    }
+    fn show(text: &str) -> String {
+        match eval(text) {
+            Ok(v) => v.to_string(),
+            Err(e) => panic!("{text}: {e}")
+        }
+    }
+    // the message without the traceback
+    fn error(text: &str) -> String {
+        eval(text).unwrap_err().to_string().lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn composition_applies_the_right_function_first() {
+        assert_eq!(show("f := x -> x + 1; g := x -> x * 2; (f ∘ g)(3)"), "7");
+        assert_eq!(show("f := x -> x + 1; g := x -> x * 2; (f .> g)(3)"), "7");
+        assert_eq!(show("(sqrt ∘ (x -> x * x))(-3)"), "3");
+    }
+    #[test]
+    fn combinators() {
+        assert_eq!(show("id(4)"), "4");
+        assert_eq!(show("const(1)(2)"), "1");
+        assert_eq!(show("flip((a, b) -> a - b)(1, 10)"), "9");
+        assert_eq!(show("(id ∘ id)([1 2])"), "[1 2]");
+    }
+    #[test]
+    fn composing_a_non_function_fails() {
+        assert_eq!(error("(1 ∘ id)(2)"), "ERROR: illegal binary operation '∘' between number and foreign-function");
+    }
+    #[test]
+    fn default_rest_and_named_parameters() {
+        assert_eq!(show("f := (x, y := 2) -> x + y; f(1)"), "3");
+        assert_eq!(show("f := (x, y := x * 10) -> x + y; f(1)"), "11");
+        assert_eq!(show("f := (x, y := 2) -> x - y; f(y: 10, x: 1)"), "-9");
+        assert_eq!(show("f := (x, ..r) -> r; f(1, 2, 3)"), "(2, 3)");
+        assert_eq!(show("f := (x, ..r) -> r; f(1)"), "()");
+    }
+    #[test]
+    fn arity_errors() {
+        assert_eq!(error("f := (x, y) -> x; f(1)"), "ERROR: expected 2 arguments, got 1");
+        assert_eq!(error("f := (x, y) -> x; f(1, 2, 3)"), "ERROR: expected 2 arguments, got 3");
+        assert_eq!(error("f := (x, y := 1) -> x; f()"), "ERROR: expected 1 to 2 arguments, got 0");
+        assert_eq!(error("f := (x, ..r) -> x; f()"), "ERROR: expected at least 1 arguments, got 0");
+    }
+    #[test]
+    fn named_arguments_are_checked_before_the_count() {
+        assert_eq!(error("f := (x, y) -> x; f(1, z: 2)"), "ERROR: z is not a parameter");
+        assert_eq!(error("f := (x, ..r) -> x; f(1, r: 2)"), "ERROR: r is not a parameter");
+        assert_eq!(error("sqrt(1, y: 2)"), "ERROR: y is not a parameter");
+        assert_eq!(error("f := (x, y) -> x; f(1, x: 2)"), "ERROR: argument x is given more than once");
+        assert_eq!(error("f := (x, y) -> x; f(y: 1, y: 2)"), "ERROR: argument y is given more than once");
+    }
+    #[test]
+    fn assignments_in_arguments_are_passed_by_position() {
+        assert_eq!(show("x := 5; f := y -> y; (f(x := 2), x)"), "(2, 2)");
+        assert_eq!(show("f := (x, y := 0) -> x - y; (f(y := 3), y)"), "(3, 3)");
+        assert_eq!(show("c := 0; x := 1; y := 2; f := v -> v; f(c ? x : y)"), "2");
+    }
+    #[test]
+    fn partial_application() {
+        assert_eq!(show("add := (a, b) -> a + b; inc := add(1, ..); inc(41)"), "42");
+        assert_eq!(show("f := (a, b, c) -> a * 100 + b * 10 + c; f(1, ..)(2, ..)(3)"), "123");
+        assert_eq!(show("f := (a, b := 0) -> a - b; f(b: 1, ..)(10)"), "9");
+        assert_eq!(show("f := (a, b := 0) -> a - b; f(b: 1, ..)(10, b: 5)"), "5");
+        assert_eq!(show("map(atan2(1, ..), [1])"), format!("[{}]", 1f64.atan2(1.0)));
+    }
+    #[test]
+    fn partial_application_needs_a_function() {
+        assert_eq!(error("x := 1; x(2, ..)"), "ERROR: expected type function, got number");
+        assert_eq!(error("f := (a, b) -> a; f(1, ..)(2, 3)"), "ERROR: expected 2 arguments, got 3");
+    }
+    #[test]
+    fn bodies_see_top_level_definitions() {
+        assert_eq!(show("k := 3; f := x -> x * k; f(2)"), "6");
+        assert_eq!(show("f := x -> x + 1; g := x -> f(x) * 2; g(1)"), "4");
+        assert_eq!(show("fact := n -> n < 2 ? 1 : n * fact(n - 1); fact(5)"), "120");
+    }
+    #[test]
+    fn bodies_do_not_see_their_callers_locals() {
+        assert_eq!(error("f := x -> y; g := y -> f(1); g(2)"), "ERROR: y is not defined");
+    }
+    #[test]
+    fn assignments_in_bodies_stay_local() {
+        assert_eq!(show("k := 1; f := x -> (k := x); f(5); k"), "1");
+        assert_eq!(show("x := 1; f := x -> x; f(7); x"), "1");
+    }
+    #[test]
+    fn conditions_only_evaluate_the_taken_branch() {
+        assert_eq!(show("1 ? 2 : undefined"), "2");
+        assert_eq!(show("0 ? undefined : 3"), "3");
+        assert_eq!(show("[] ? 1 : 2"), "2");
+    }
+    // runs on a thread smaller than any an embedder would use, the limits have to hold there without overflowing
+    fn small_thread<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+        std::thread::Builder::new().stack_size(1024 * 1024).spawn(f).unwrap().join().unwrap()
+    }
+    #[test]
+    fn deep_recursion_on_a_small_thread() {
+        assert_eq!(small_thread(|| show("g := n -> n = 0 ? 0 : 1 + g(n - 1); g(3000)")), "3000");
+        let e = small_thread(|| error("g := n -> n = 0 ? 0 : 1 + g(n - 1); g(100000)"));
+        assert_eq!(e, format!("ERROR: maximum recursion depth of {MAX_DEPTH} exceeded"));
+    }
+    #[test]
+    fn deep_nesting_on_a_small_thread() {
+        let text = format!("{}1{}", "(".repeat(900), ")".repeat(900));
+        assert_eq!(small_thread(move || show(&text)), "1");
+        let text = format!("{}1{}", "(".repeat(5000), ")".repeat(5000));
+        let e = small_thread(move || error(&text));
+        assert_eq!(e, format!("ERROR: maximum recursion depth of {} exceeded", crate::parser::MAX_NESTING));
+    }
+    #[test]
+    fn tail_calls_run_in_constant_space() {
+        assert_eq!(small_thread(|| show("loop := (n, acc) -> n = 0 ? acc : loop(n - 1, acc + 1); loop(100000, 0)")), "100000");
+    }
+    // writes the files into a fresh directory and evaluates `main.eq` from it
+    fn run_files(name: &str, files: &[(&str, &str)]) -> Result<Value, Error> {
+        let dir = std::env::temp_dir().join(format!("equa-{}-{name}", std::process::id()));
+        for (file, text) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        let value = Engine::new().eval_file(dir.join("main.eq").to_str().unwrap()).map(|v| v.unwrap());
+        std::fs::remove_dir_all(dir).unwrap();
+        value
+    }
+    #[test]
+    fn imports_expose_definitions_as_fields() {
+        let files = [
+            ("main.eq", "m :: import \"lib/geo.eq\"; m.area(2) + m.k"),
+            ("lib/geo.eq", "k :: 1; sq :: import \"sq.eq\"; area :: r -> sq.sq(r) * k"),
+            ("lib/sq.eq", "sq :: x -> x * x"),
+        ];
+        assert_eq!(run_files("fields", &files).unwrap().to_string(), "5");
+    }
+    #[test]
+    fn import_errors() {
+        let e = run_files("missing", &[("main.eq", "import \"nope.eq\"")]).unwrap_err();
+        assert!(e.to_string().starts_with("ERROR: could not import "), "{e}");
+        let e = run_files("cycle", &[("main.eq", "import \"a.eq\""), ("a.eq", "import \"b.eq\""), ("b.eq", "import \"a.eq\"")]).unwrap_err();
+        assert!(e.to_string().starts_with("ERROR: import cycle through "), "{e}");
+        let e = run_files("field", &[("main.eq", "m :: import \"a.eq\"; m.y"), ("a.eq", "x :: 1")]).unwrap_err();
+        assert!(e.to_string().ends_with("a.eq has no field y"), "{e}");
+    }
+    #[test]
+    fn imports_outside_files_need_a_base_directory() {
+        let dir = std::env::temp_dir().join(format!("equa-{}-base", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("k.eq"), "k :: 7").unwrap();
+        let mut engine = Engine::new();
+        let e = engine.eval("import \"k.eq\"").unwrap_err();
+        assert_eq!(e.to_string().lines().next(), Some("ERROR: could not import k.eq: <engine> is not a file and no base directory is set"));
+        let absolute = format!("(import \"{}\").k", dir.join("k.eq").display());
+        assert_eq!(engine.eval(&absolute).unwrap().unwrap().to_string(), "7");
+        engine.set_base(&dir);
+        assert_eq!(engine.eval("(import \"k.eq\").k").unwrap().unwrap().to_string(), "7");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn sequences_return_their_last_statement() {
+        assert_eq!(show("a := 1; b := a + 1; a + b"), "3");
+        assert_eq!(show("1;"), "1");
+        assert_eq!(show(";"), "()");
+        assert_eq!(error("a := 1; a := b; a"), "ERROR: b is not defined");
+    }
+    #[test]
+    fn answers_are_numbered() {
+        let mut context = Context::new();
+        assert_eq!(context.answer(&Value::Number(Number::Int(4))), 1);
+        assert_eq!(context.answer(&Value::Number(Number::Int(5))), 2);
+        assert_eq!(crate::run("ans1 * 10 + ans", &mut context, "stdin").unwrap().unwrap().to_string(), "45");
+        // definitions shadow the answers
+        assert_eq!(crate::run("ans := 0; ans + ans2", &mut context, "stdin").unwrap().unwrap().to_string(), "5");
+        context.reset();
+        assert_eq!(crate::run("ans", &mut context, "stdin").unwrap().unwrap().to_string(), "5");
+    }
+    // the traceback lines of an error, without the message and the excerpt
+    fn traceback(text: &str) -> Vec<String> {
+        let e = eval(text).unwrap_err().render("<engine>", text);
+        e.lines().skip_while(|l| !l.starts_with("traceback")).skip(1).map(|l| l.trim().to_string()).collect()
+    }
+    #[test]
+    fn tracebacks_collapse_calls_from_one_site() {
+        assert_eq!(traceback("f := n -> n = 0 ? x : 1 + f(n - 1); f(100)"), vec![
+            "in f(100) at <engine>:1:37", "in f(99) at <engine>:1:27", "… repeated 98 times", "in f(0) at <engine>:1:27"
+        ]);
+        assert_eq!(traceback("f := (n, ..r) -> n = 0 ? x : 1 + f(n - 1, r, k: 1); f(3, 7, 8)"), vec![
+            "in f(3, 7, 8) at <engine>:1:53", "in f(2, (7, 8), k: 1) at <engine>:1:34"
+        ]);
+    }
+    #[test]
+    fn tracebacks_note_tail_calls() {
+        assert_eq!(traceback("g := n -> n = 0 ? y : g(n - 1); 1 + g(5)"), vec!["in g(5) at <engine>:1:37", "… 5 tail calls elided"]);
+        assert_eq!(traceback("g := n -> n = 0 ? y : g(n - 1); 1 + g(0)"), vec!["in g(0) at <engine>:1:37"]);
+    }
+    #[test]
+    fn tracebacks_are_capped() {
+        let lines = traceback("a := n -> n = 0 ? z : 1 + b(n - 1); b := n -> 1 + a(n - 1); a(100)");
+        assert_eq!(lines.len(), crate::error::MAX_FRAMES + 1);
+        assert_eq!(lines[0], "in a(100) at <engine>:1:61");
+        assert_eq!(lines[crate::error::MAX_FRAMES / 2], "… 61 more frames");
+        assert_eq!(lines.last().unwrap(), "in a(0) at <engine>:1:51");
+    }
+    fn limited(limits: Limits, text: &str) -> Result<Value, Error> {
+        let mut engine = Engine::new();
+        *engine.limits() = limits;
+        engine.eval(text).map(|v| v.unwrap())
+    }
+    #[test]
+    fn limits_stop_evaluation() {
+        let fuel = Limits { fuel: Some(100), ..Limits::default() };
+        assert_eq!(limited(fuel, "f := n -> f(n + 1); f(0)").unwrap_err().to_string().lines().next(), Some("ERROR: evaluation ran out of fuel after 100 steps"));
+        let size = Limits { max_size: Some(4), ..Limits::default() };
+        assert_eq!(limited(size.clone(), "[1 2] ++ [3 4]").unwrap().to_string(), "[1 2 3 4]");
+        assert_eq!(limited(size, "[1 2] ++ [3 4 5]").unwrap_err().to_string().lines().next(), Some("ERROR: collection exceeds the maximum size of 4 elements"));
+        let depth = Limits { max_depth: 50, ..Limits::default() };
+        assert_eq!(limited(depth, "f := n -> n = 0 ? 0 : 1 + f(n - 1); f(100)").unwrap_err().to_string().lines().next(), Some("ERROR: maximum recursion depth of 50 exceeded"));
+    }
+    #[test]
+    fn the_timeout_covers_parsing() {
+        let limits = Limits { timeout: Some(Duration::ZERO), ..Limits::default() };
+        assert_eq!(limited(limits.clone(), "1"), Err(Error::Timeout(Duration::ZERO)));
+        let mut context = Context::new();
+        context.limits = limits;
+        assert_eq!(crate::vm::run("1", &mut context, "test"), Err(Error::Timeout(Duration::ZERO)));
+        let mut engine = Engine::new();
+        *engine.limits() = Limits { timeout: Some(Duration::ZERO), ..Limits::default() };
+        assert!(engine.compile("1").is_err());
+    }
+    #[test]
+    fn frames_share_scopes_until_they_write() {
+        let mut context = Context::new();
+        crate::run("k := 1", &mut context, "test").unwrap();
+        let mut frame = context.frame();
+        assert!(Rc::ptr_eq(&context.scopes()[0], &frame.scopes()[0]));
+        frame.set(&"k".to_string(), &Value::Number(Number::Int(2))).unwrap();
+        assert!(!Rc::ptr_eq(&context.scopes()[0], &frame.scopes()[0]));
+        assert_eq!(context.get(&"k".to_string()).unwrap().to_string(), "1");
+        assert_eq!(frame.get(&"k".to_string()).unwrap().to_string(), "2");
+    }
+    #[test]
+    fn scratch_contexts_throw_their_writes_away() {
+        let mut context = Context::new();
+        crate::run("k := 1; f := x -> x + k", &mut context, "test").unwrap();
+        let steps = context.steps();
+        let mut scratch = context.scratch();
+        assert_eq!(crate::run("k := 5; j := 2; f(j)", &mut scratch, "test").unwrap().unwrap().to_string(), "7");
+        assert_eq!(context.get(&"k".to_string()).unwrap().to_string(), "1");
+        assert!(context.get(&"j".to_string()).is_none());
+        assert_eq!(context.steps(), steps);
+    }
+    #[test]
+    fn copies_share_contents_until_changed() {
+        let mut engine = Engine::new();
+        engine.eval("a := [1 2]; b := a").unwrap();
+        let (Some(Value::Vector(a)), Some(Value::Vector(b))) = (engine.get("a"), engine.get("b")) else { panic!() };
+        assert!(Rc::ptr_eq(a, b));
+        engine.eval("b := b ++ [3]").unwrap();
+        assert_eq!(engine.get("a").unwrap().to_string(), "[1 2]");
+        assert_eq!(engine.get("b").unwrap().to_string(), "[1 2 3]");
+    }
+    #[test]
+    fn memo_caches_count_against_the_size_limit() {
+        let size = Limits { max_size: Some(3), ..Limits::default() };
+        assert_eq!(limited(size.clone(), "m := memo(x -> x * 2); m(1) + m(1) + m(2) + m(3)").unwrap().to_string(), "14");
+        let e = limited(size, "m := memo(x -> x * 2); m(1) + m(2) + m(3) + m(4)").unwrap_err();
+        assert_eq!(e.to_string().lines().next(), Some("ERROR: collection exceeds the maximum size of 3 elements"));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::set::*;
+use crate::value::*;
+use crate::error::*;
+use serde_json::{Value as Json, Map};
+
+// vectors and tuples are plain arrays, sets and functions are tagged objects
+pub fn encode(value: &Value) -> Result<Json, Error> {
+    match value {
+        Value::Number(Number::Int(v)) => Ok(Json::from(*v)),
+        Value::Number(Number::Float(v)) => match serde_json::Number::from_f64(*v) {
+            Some(num) => Ok(Json::Number(num)),
+            None => Err(Error::Json(format!("{v} has no json representation")))
+        }
+        Value::Vector(values) | Value::Tuple(values) => Ok(Json::Array(values.iter().map(encode).collect::<Result<_, _>>()?)),
+        Value::Set(values) => {
+            let values = values.values.iter().map(encode).collect::<Result<_, _>>()?;
+            Ok(Json::Object(Map::from_iter([("set".to_string(), Json::Array(values))])))
+        }
+        Value::Str(v) => Ok(Json::String(v.clone())),
+        Value::Module(name, _) => Err(Error::Json(format!("module {name} has no json representation"))),
+        value => Ok(Json::Object(Map::from_iter([("function".to_string(), Json::String(value.to_string()))]))),
+    }
+}
+pub fn decode(json: &Json) -> Result<Value, Error> {
+    match json {
+        Json::Number(num) => match num.as_i64() {
+            Some(v) => Ok(Value::Number(Number::Int(v))),
+            None => Ok(Value::Number(Number::Float(num.as_f64().unwrap_or(f64::NAN)))),
+        }
+        Json::String(v) => Ok(Value::Str(v.clone())),
+        Json::Bool(v) => Ok(Value::Number(Number::Int(*v as i64))),
+        Json::Array(values) => Ok(Value::Vector(values.iter().map(decode).collect::<Result<Vec<Value>, _>>()?.into())),
+        Json::Object(map) => match (map.len(), map.get("set")) {
+            (1, Some(Json::Array(values))) => {
+                let mut set: Set<Value> = Set::new();
+                for v in values { set.add(decode(v)?) }
+                Ok(Value::Set(set.into()))
+            }
+            _ if map.contains_key("function") => Err(Error::Json("functions can't be read from json".to_string())),
+            _ => Err(Error::Json(format!("unexpected object {json}")))
+        }
+        json => Err(Error::Json(format!("unexpected {json}")))
+    }
+}
+
+pub fn to_json(value: &Value) -> Result<String, Error> {
+    Ok(encode(value)?.to_string())
+}
+pub fn from_json(text: &str) -> Result<Value, Error> {
+    match serde_json::from_str::<Json>(text) {
+        Ok(json) => decode(&json),
+        Err(e) => Err(Error::Json(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    fn value(text: &str) -> Value {
+        Engine::new().eval(text).unwrap().unwrap()
+    }
+    fn round_trip(text: &str) -> String {
+        let v = value(text);
+        from_json(&to_json(&v).unwrap()).unwrap().to_string()
+    }
+
+    #[test]
+    fn values_round_trip() {
+        for text in ["1", "-2.5", "[1 2.5 [3 4]]", "{1 2 3}", "[{1 [2]} {}]", "[]"] {
+            assert_eq!(round_trip(text), value(text).to_string(), "{text}");
+        }
+        assert_eq!(to_json(&value("2.0")).unwrap(), "2.0");
+        assert_eq!(round_trip("2.0"), value("2.0").to_string());
+    }
+    #[test]
+    fn encodings() {
+        assert_eq!(to_json(&value("[1 {2}]")).unwrap(), r#"[1,{"set":[2]}]"#);
+        assert_eq!(to_json(&value("(1, [2])")).unwrap(), "[1,[2]]");
+        assert_eq!(to_json(&value("x -> x + 1")).unwrap(), r#"{"function":"function(x)->(x + 1)"}"#);
+        assert_eq!(round_trip("(1, 2)"), "[1 2]");
+        assert_eq!(from_json("true").unwrap(), Value::Number(Number::Int(1)));
+    }
+    #[test]
+    fn what_json_cant_hold() {
+        assert_eq!(to_json(&value("inf")), Err(Error::Json("inf has no json representation".to_string())));
+        assert_eq!(from_json(r#"{"function":"x -> x"}"#), Err(Error::Json("functions can't be read from json".to_string())));
+        assert_eq!(from_json(r#"{"a":1}"#), Err(Error::Json(r#"unexpected object {"a":1}"#.to_string())));
+        assert_eq!(from_json("null"), Err(Error::Json("unexpected null".to_string())));
+        assert!(from_json("[1,").is_err());
+    }
+}
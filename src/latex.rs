@@ -0,0 +1,279 @@
+use crate::value::*;
+use crate::lexer::*;
+use crate::parser::*;
+use crate::format::precedence;
+
+pub const GREEK: [&str; 24] = ["alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa", "lambda", "mu",
+    "nu", "xi", "omicron", "pi", "rho", "sigma", "tau", "upsilon", "phi", "chi", "psi", "omega"];
+pub const CAPITALS: [&str; 11] = ["Gamma", "Delta", "Theta", "Lambda", "Xi", "Pi", "Sigma", "Upsilon", "Phi", "Psi", "Omega"];
+const OPERATORS: [(&str, &str); 12] = [("sin", "\\sin"), ("cos", "\\cos"), ("tan", "\\tan"), ("asin", "\\arcsin"), ("acos", "\\arccos"),
+    ("atan", "\\arctan"), ("sinh", "\\sinh"), ("cosh", "\\cosh"), ("tanh", "\\tanh"), ("asinh", "\\operatorname{arsinh}"),
+    ("acosh", "\\operatorname{arcosh}"), ("atanh", "\\operatorname{artanh}")];
+
+pub fn text(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '{' | '}' | '_' | '#' | '%' | '&' | '$' => { out.push('\\'); out.push(c) }
+            c => out.push(c),
+        }
+    }
+    format!("\\text{{{out}}}")
+}
+pub fn word(v: &str) -> String {
+    match v {
+        "PI" => return "\\pi".to_string(),
+        "TAU" => return "\\tau".to_string(),
+        "E" => return "e".to_string(),
+        "inf" => return "\\infty".to_string(),
+        "NaN" => return "\\mathrm{NaN}".to_string(),
+        _ => {}
+    }
+    if v == "omicron" { return "o".to_string() }
+    if GREEK.contains(&v) || CAPITALS.contains(&v) { return format!("\\{v}") }
+    if let Some((name, index)) = v.split_once('_') {
+        if !name.is_empty() && !index.is_empty() { return format!("{}_{{{}}}", word(name), word(index)) }
+    }
+    if v.chars().count() == 1 { return v.to_string() }
+    format!("\\mathrm{{{}}}", v.replace('_', "\\_"))
+}
+fn float(v: f64) -> String {
+    if v.is_nan() { return "\\mathrm{NaN}".to_string() }
+    if v.is_infinite() { return if v > 0.0 { "\\infty".to_string() } else { "-\\infty".to_string() } }
+    v.to_string()
+}
+
+// fractions are set apart visually, so they never need parentheses around them
+fn strength(node: &Node) -> usize {
+    match node {
+        Node::Binary { op: Token::Slash, .. } => 14,
+        node => precedence(node),
+    }
+}
+fn operand(node: &Node, min: usize) -> String {
+    if strength(node) < min { return format!("\\left({}\\right)", latex(node)) }
+    latex(node)
+}
+fn list(nodes: &[Node]) -> String {
+    nodes.iter().map(latex).collect::<Vec<String>>().join(", ")
+}
+// a function of a single plain parameter, as used by the set-builder and integral forms
+pub fn lambda(node: &Node) -> Option<(&str, &Node)> {
+    if let Node::Function { id, body, .. } = node {
+        match id.as_ref() {
+            Node::Word { v, .. } => return Some((v, body)),
+            Node::Tuple { nodes, .. } if nodes.len() == 1 => if let Node::Word { v, .. } = &nodes[0] { return Some((v, body)) }
+            _ => {}
+        }
+    }
+    None
+}
+fn call(v: &Node, args: &[Node], partial: bool) -> String {
+    if let (Node::Word { v: name, .. }, false) = (v, partial) {
+        match (name.as_str(), args) {
+            ("sqrt", [x]) => return format!("\\sqrt{{{}}}", latex(x)),
+            ("abs", [x]) => return format!("\\left|{}\\right|", latex(x)),
+            ("floor", [x]) => return format!("\\left\\lfloor {}\\right\\rfloor", latex(x)),
+            ("ceil", [x]) => return format!("\\left\\lceil {}\\right\\rceil", latex(x)),
+            ("filter", [p, s]) => if let Some((x, body)) = lambda(p) {
+                return format!("\\left\\{{{} \\in {} \\mid {}\\right\\}}", word(x), latex(s), latex(body))
+            }
+            ("map", [f, s]) => if let Some((x, body)) = lambda(f) {
+                return format!("\\left\\{{{} \\mid {} \\in {}\\right\\}}", latex(body), word(x), latex(s))
+            }
+            ("integrate", [f, a, b]) => if let Some((x, body)) = lambda(f) {
+                return format!("\\int_{{{}}}^{{{}}} {} \\, d{}", latex(a), latex(b), latex(body), word(x))
+            }
+            _ => {}
+        }
+        if let Some((_, op)) = OPERATORS.iter().find(|(id, _)| id == name) {
+            return format!("{op}\\left({}\\right)", list(args))
+        }
+    }
+    let mut args = args.iter().map(latex).collect::<Vec<String>>();
+    if partial { args.push("\\ldots".to_string()) }
+    format!("{}\\left({}\\right)", operand(v, 12), args.join(", "))
+}
+fn matrix(rows: Vec<Vec<String>>) -> String {
+    let rows = rows.into_iter().map(|x| x.join(" & ")).collect::<Vec<String>>();
+    format!("\\begin{{bmatrix}} {} \\end{{bmatrix}}", rows.join(" \\\\ "))
+}
+
+/// Renders an expression as it was written.
+pub fn latex(node: &Node) -> String {
+    match node {
+        Node::Int { v, .. } => v.to_string(),
+        Node::Float { v, .. } => float(*v),
+        Node::Word { v, .. } => word(v),
+        Node::Vector { v, .. } => {
+            let rows = v.iter().map(|x| match x { Node::Vector { v, .. } => Some(v.iter().map(latex).collect()), _ => None });
+            match rows.collect::<Option<Vec<Vec<String>>>>() {
+                Some(rows) if !rows.is_empty() => matrix(rows),
+                _ => format!("\\left[{}\\right]", list(v)),
+            }
+        }
+        Node::Set { v, .. } if v.values.is_empty() => "\\emptyset".to_string(),
+        Node::Set { v, .. } => format!("\\left\\{{{}\\right\\}}", list(&v.values)),
+        Node::Tuple { nodes, .. } => format!("\\left({}\\right)", list(nodes)),
+        Node::Binary { op: Token::Slash, left, right, .. } => format!("\\frac{{{}}}{{{}}}", latex(left), latex(right)),
+        Node::Binary { op: Token::Exponent, left, right, .. } => {
+            let base = match left.as_ref() {
+                Node::Binary { op: Token::Slash, .. } => format!("\\left({}\\right)", latex(left)),
+                left => operand(left, 12),
+            };
+            format!("{base}^{{{}}}", latex(right))
+        }
+        Node::Binary { op: Token::Field, left, right, .. } => format!("{}.{}", operand(left, 13), operand(right, 14)),
+        Node::Binary { op, left, right, .. } => {
+            let symbol = match op {
+                Token::Star => "\\cdot".to_string(),
+                Token::PlusMinus => "\\pm".to_string(),
+                Token::NotEqual => "\\neq".to_string(),
+                Token::LessEqual => "\\leq".to_string(),
+                Token::GreaterEqual => "\\geq".to_string(),
+                Token::Concat => "\\mathbin{+\\!\\!+}".to_string(),
+                Token::Remove => "\\setminus".to_string(),
+                Token::Compose => "\\circ".to_string(),
+                op => op.to_string(),
+            };
+            let prec = precedence(node);
+            format!("{} {symbol} {}", operand(left, prec), operand(right, prec + 1))
+        }
+        Node::Unary { op: Token::Minus, node, .. } => format!("-{}", operand(node, 10)),
+        Node::Unary { op: Token::Amount, node, .. } => format!("\\#{}", operand(node, 12)),
+        Node::Unary { node, .. } => format!("\\ldots {}", operand(node, 14)),
+        Node::Percent { node, .. } => format!("{}\\%", operand(node, 10)),
+        Node::Abs { node, .. } => format!("\\left|{}\\right|", latex(node)),
+        Node::Call { v, args, partial, .. } => call(v, args, *partial),
+        Node::Assign { m: false, id, expr, .. } => match expr.as_ref() {
+            Node::Function { id: params, body, .. } => {
+                let params = match params.as_ref() { Node::Tuple { nodes, .. } => list(nodes), params => latex(params) };
+                format!("{}\\left({params}\\right) = {}", latex(id), latex(body))
+            }
+            expr => format!("{} = {}", latex(id), latex(expr)),
+        }
+        Node::Assign { m: true, id, expr, .. } => format!("{} := {}", latex(id), operand(expr, 1)),
+        Node::Function { id, body, .. } => format!("{} \\mapsto {}", operand(id, 3), latex(body)),
+        Node::Condition { .. } => {
+            let mut rows: Vec<String> = vec![];
+            let mut node = node;
+            while let Node::Condition { cond, case, default, .. } = node {
+                rows.push(format!("{} & \\text{{if }} {}", latex(case), latex(cond)));
+                node = default;
+            }
+            rows.push(format!("{} & \\text{{otherwise}}", latex(node)));
+            format!("\\begin{{cases}} {} \\end{{cases}}", rows.join(" \\\\ "))
+        }
+        Node::Import { path, .. } => text(&format!("import \"{path}\"")),
+        Node::Block { nodes, .. } => nodes.iter().map(latex).collect::<Vec<String>>().join(" \\\\ "),
+        Node::Named { id, expr, .. } => format!("{}: {}", word(id), latex(expr)),
+        Node::Error { .. } => "?".to_string(),
+    }
+}
+
+fn param(param: &Param) -> String {
+    match (&param.default, param.rest) {
+        (_, true) => format!("\\ldots {}", word(&param.id)),
+        (Some(default), _) => format!("{} := {}", word(&param.id), latex(default)),
+        (None, _) => word(&param.id),
+    }
+}
+
+/// Renders a value, functions are shown as their definition.
+pub fn value(value: &Value) -> String {
+    let list = |values: &[Value]| values.iter().map(self::value).collect::<Vec<String>>().join(", ");
+    match value {
+        Value::Number(Number::Int(v)) => v.to_string(),
+        Value::Number(Number::Float(v)) => float(*v),
+        Value::Vector(values) => {
+            let rows = values.iter().map(|x| match x { Value::Vector(v) => Some(v.iter().map(self::value).collect()), _ => None });
+            match rows.collect::<Option<Vec<Vec<String>>>>() {
+                Some(rows) if !rows.is_empty() => matrix(rows),
+                _ => format!("\\left[{}\\right]", list(values)),
+            }
+        }
+        Value::Set(values) if values.is_empty() => "\\emptyset".to_string(),
+        Value::Set(values) => format!("\\left\\{{{}\\right\\}}", list(&values.values)),
+        Value::Tuple(values) => format!("\\left({}\\right)", list(values)),
+        Value::Function(params, body) => match params.as_slice() {
+            [p] if p.default.is_none() && !p.rest => format!("{} \\mapsto {}", word(&p.id), latex(body)),
+            params => format!("\\left({}\\right) \\mapsto {}", params.iter().map(param).collect::<Vec<String>>().join(", "), latex(body)),
+        }
+        Value::ForeignFunction(params, _) => format!("{}\\left({}\\right)", text("foreign-function"),
+            params.iter().map(|x| word(&x.id)).collect::<Vec<String>>().join(", ")),
+        Value::Composed(func, other) => format!("\\left({}\\right) \\circ \\left({}\\right)", self::value(func), self::value(other)),
+        Value::Memo(func, _) | Value::Scoped(func, _) => self::value(func),
+        Value::Compiled(proto) => self::value(&proto.function),
+        Value::Partial(func, args, named) => {
+            let mut args = args.iter().map(self::value).collect::<Vec<String>>();
+            args.extend(named.iter().map(|(id, x)| format!("{}: {}", word(id), self::value(x))));
+            args.push("\\ldots".to_string());
+            format!("\\left({}\\right)\\left({}\\right)", self::value(func), args.join(", "))
+        }
+        Value::Module(path, _) => text(&format!("module {path}")),
+        Value::Str(v) => text(v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    fn tex(text: &str) -> String {
+        let (tokens, poses) = lex(text, "test").unwrap();
+        latex(&parse(tokens, poses, "test").unwrap())
+    }
+    fn shown(text: &str) -> String {
+        value(&Engine::new().eval(text).unwrap().unwrap())
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(tex("1/2 + x"), r"\frac{1}{2} + x");
+        assert_eq!(tex("(1/2)^2"), r"\left(\frac{1}{2}\right)^{2}");
+        assert_eq!(tex("x^(2+1)"), r"x^{2 + 1}");
+        assert_eq!(tex("a * (b + c)"), r"a \cdot \left(b + c\right)");
+        assert_eq!(tex("-x^2"), r"\left(-x\right)^{2}");
+        assert_eq!(tex("1 +- 2"), r"1 \pm 2");
+        assert_eq!(tex("a ++ b"), r"a \mathbin{+\!\!+} b");
+        assert_eq!(tex("50% + #v + |x|"), r"50\% + \#v + \left|x\right|");
+        assert_eq!(tex("f(1, ..)"), r"f\left(1, \ldots\right)");
+        assert_eq!(tex("f(1, b: 2)"), r"f\left(1, b: 2\right)");
+        assert_eq!(tex("[[1 2] [3 4]]"), r"\begin{bmatrix} 1 & 2 \\ 3 & 4 \end{bmatrix}");
+        assert_eq!(tex("{} ++ {1 2}"), r"\emptyset \mathbin{+\!\!+} \left\{1, 2\right\}");
+    }
+    #[test]
+    fn names_and_functions() {
+        assert_eq!(tex("alpha_1 + theta"), r"\alpha_{1} + \theta");
+        assert_eq!(tex("speed_max"), r"\mathrm{speed}_{\mathrm{max}}");
+        assert_eq!(tex("PI * r^2"), r"\pi \cdot r^{2}");
+        assert_eq!(tex("sqrt(x) + sin(x)"), r"\sqrt{x} + \sin\left(x\right)");
+        assert_eq!(tex("f :: x -> x^2"), r"f\left(x\right) = x^{2}");
+        assert_eq!(tex("f :: (x, y) -> x*y"), r"f\left(x, y\right) = x \cdot y");
+        assert_eq!(tex("a := 2"), r"a := 2");
+        assert_eq!(tex("x -> x"), r"x \mapsto x");
+        assert_eq!(tex("x < 0 ? -x : x"), r"\begin{cases} -x & \text{if } x < 0 \\ x & \text{otherwise} \end{cases}");
+    }
+    #[test]
+    fn set_builders_and_integrals() {
+        assert_eq!(tex("filter(x -> x > 2, s)"), r"\left\{x \in s \mid x > 2\right\}");
+        assert_eq!(tex("map(x -> x^2, s)"), r"\left\{x^{2} \mid x \in s\right\}");
+        assert_eq!(tex("integrate(x -> x^2, 0, 1)"), r"\int_{0}^{1} x^{2} \, dx");
+    }
+    #[test]
+    fn values() {
+        assert_eq!(shown("[[1 2] [3 4]]"), r"\begin{bmatrix} 1 & 2 \\ 3 & 4 \end{bmatrix}");
+        assert_eq!(shown("{}"), r"\emptyset");
+        assert_eq!(shown("(1, 2.5)"), r"\left(1, 2.5\right)");
+        assert_eq!(shown("inf"), r"\infty");
+        assert_eq!(shown("x -> x^2"), r"x \mapsto x^{2}");
+        assert_eq!(shown("memo(x -> x)"), r"x \mapsto x");
+        assert_eq!(shown("(x, y := 2) -> x"), r"\left(x, y := 2\right) \mapsto x");
+        assert_eq!(shown("sqrt"), r"\text{foreign-function}\left(x\right)");
+        assert_eq!(shown("((a, b) -> a)(1, ..)"), r"\left(\left(a, b\right) \mapsto a\right)\left(1, \ldots\right)");
+    }
+}
@@ -0,0 +1,147 @@
+extern crate logos;
+pub mod set;
+pub mod position;
+pub mod value; use value::*;
+pub mod error; use error::*;
+pub mod lexer;
+pub mod native;
+pub mod numeric;
+pub mod parser;
+pub mod interpreter; use interpreter::*;
+pub mod vm;
+pub mod builtins;
+pub mod json;
+pub mod format;
+pub mod latex;
+pub mod unicode;
+pub mod mathml;
+
+pub fn run(text: &str, context: &mut Context, path: &str) -> Result<Option<Value>, Error> {
+    context.start();
+    let (tokens, poses) = lexer::lex(text, path)?;
+    if tokens.is_empty() { return Ok(None) }
+    let node = parser::parse(tokens, poses, path)?;
+    context.check_time()?;
+    let value = interpreter::get(&node, context, path)?;
+    Ok(Some(value))
+}
+
+/// An interpreter with the standard functions, for evaluating equa from rust.
+pub struct Engine {
+    context: Context,
+}
+impl Engine {
+    pub fn new() -> Self {
+        let mut context = Context::new();
+        builtins::std_context(&mut context).expect("a new context binds nothing");
+        Self { context }
+    }
+    /// Evaluates `text`, returning the value of its last expression.
+    pub fn eval(&mut self, text: &str) -> Result<Option<Value>, Error> {
+        run(text, &mut self.context, "<engine>")
+    }
+    /// Evaluates the file at `path`, relative imports are resolved from its directory.
+    pub fn eval_file(&mut self, path: &str) -> Result<Option<Value>, Error> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => return Err(Error::Import(path.to_string(), e.to_string()))
+        };
+        run(&text, &mut self.context, path)
+    }
+    /// Compiles `text` once, so it can be run many times with `run` on the virtual machine. Compiling counts against the timeout too.
+    pub fn compile(&mut self, text: &str) -> Result<vm::Program, Error> {
+        self.context.start();
+        let (tokens, poses) = lexer::lex(text, "<engine>")?;
+        let node = if tokens.is_empty() { parser::Node::Block { nodes: vec![], pos: position::Position("<engine>".to_string(), 0..0) } } else { parser::parse(tokens, poses, "<engine>")? };
+        self.context.check_time()?;
+        Ok(vm::compile(&node, "<engine>"))
+    }
+    /// Runs a compiled program, the functions it defines stay compiled.
+    pub fn run(&mut self, program: &vm::Program) -> Result<Value, Error> {
+        self.context.start();
+        vm::execute(program, &mut self.context)
+    }
+    /// Calls a function with `args`, compiled functions run on the virtual machine.
+    pub fn call(&mut self, function: &Value, args: Vec<Value>) -> Result<Value, Error> {
+        self.context.start();
+        interpreter::call(function, args, &mut self.context, "<engine>")
+    }
+    /// The limits each call to `eval` runs under.
+    pub fn limits(&mut self) -> &mut Limits {
+        &mut self.context.limits
+    }
+    /// Resolves relative imports in `eval`ed text from `dir`, by default they are an error since the text has no file to be relative to.
+    pub fn set_base(&mut self, dir: &std::path::Path) {
+        self.context.set_base(dir)
+    }
+    pub fn get(&self, id: &str) -> Option<&Value> {
+        self.context.get(&id.to_string())
+    }
+    /// Binds `id` to `value` as a mutable variable.
+    pub fn set(&mut self, id: &str, value: Value) -> Result<(), Error> {
+        self.context.set(&id.to_string(), &value)
+    }
+    /// Binds `id` to `value` as an immutable variable.
+    pub fn set_const(&mut self, id: &str, value: Value) -> Result<(), Error> {
+        self.context.set_const(&id.to_string(), &value)
+    }
+    /// Registers a rust function under `id`, its arguments are converted from the values bound to `params`.
+    pub fn register<Args, P: Into<Param> + Clone>(&mut self, id: &str, params: &[P], function: impl native::IntoNative<Args>) -> Result<(), Error> {
+        self.context.global_const(&id.to_string(), &native::typed(params, function))
+    }
+    /// Registers a rust function under `id` that receives the raw arguments, the calling context and the caller's path.
+    pub fn register_native<P: Into<Param> + Clone>(&mut self, id: &str, params: &[P], function: impl Fn(&[Value], &mut Context, &str) -> Result<Value, Error> + 'static) -> Result<(), Error> {
+        self.context.global_const(&id.to_string(), &native::native(params, function))
+    }
+}
+impl Default for Engine {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(v: i64) -> Value { Value::Number(Number::Int(v)) }
+
+    #[test]
+    fn engine_exposes_values_and_functions() {
+        let mut engine = Engine::new();
+        engine.set_const("k", int(4)).unwrap();
+        engine.register_native("twice", &["f", "x"], |args, context, path| {
+            let once = interpreter::call(&args[0], vec![args[1].clone()], context, path)?;
+            interpreter::call(&args[0], vec![once], context, path)
+        }).unwrap();
+        assert_eq!(engine.eval("twice(x -> x * 2, k)").unwrap(), Some(int(16)));
+        assert_eq!(engine.eval("").unwrap(), None);
+        engine.set("m", int(1)).unwrap();
+        engine.eval("m := m + 1").unwrap();
+        assert_eq!(engine.get("m"), Some(&int(2)));
+    }
+    #[test]
+    fn engine_rejects_redefinitions() {
+        let mut engine = Engine::new();
+        engine.set_const("k", int(4)).unwrap();
+        assert_eq!(engine.set("k", int(5)), Err(Error::Immutable("k".to_string())));
+        assert_eq!(engine.set_const("k", int(5)), Err(Error::AlreadyDefined("k".to_string())));
+        assert_eq!(engine.set("sqrt", int(5)), Err(Error::Immutable("sqrt".to_string())));
+        assert_eq!(engine.register_native("sqrt", &["x"], |args, _, _| Ok(args[0].clone())), Err(Error::AlreadyDefined("sqrt".to_string())));
+        assert_eq!(engine.get("k"), Some(&int(4)));
+    }
+    #[test]
+    fn engine_runs_compiled_programs() {
+        let mut engine = Engine::new();
+        let program = engine.compile("f :: (x, a) -> a * x^2 + 1").unwrap();
+        engine.run(&program).unwrap();
+        let f = engine.get("f").unwrap().clone();
+        assert_eq!(engine.call(&f, vec![int(2), int(3)]).unwrap(), int(13));
+        let empty = engine.compile("").unwrap();
+        assert_eq!(engine.run(&empty).unwrap().to_string(), "()");
+        assert_eq!(engine.call(&f, vec![int(2)]).unwrap_err().to_string(), "ERROR: expected 2 arguments, got 1");
+    }
+    #[test]
+    fn engine_reports_missing_files() {
+        let e = Engine::new().eval_file("/nonexistent/main.eq").unwrap_err();
+        assert!(matches!(e, Error::Import(path, _) if path == "/nonexistent/main.eq"));
+    }
+}
@@ -1,279 +1,418 @@
-#![allow(dead_code)]
-#![allow(unused)]
+use equa::*;
+use equa::error::*;
+use equa::value::*;
+use equa::interpreter::*;
+use equa::lexer::{self, Token};
+use equa::parser;
+use std::{io, io::Read, io::IsTerminal};
+use rustyline::{DefaultEditor, error::ReadlineError};
 
-extern crate logos;
-mod set;
-mod position;
-mod value; use value::*;
-mod error; use error::*;
-mod lexer;
-mod parser;
-mod interpreter; use interpreter::*;
-use std::{io, io::Write};
+pub const USAGE: &str = "usage: equa [--vm] [--max-depth N] [--fuel N] [--max-size N] [--timeout MS] [--json | --latex | --unicode | --mathml] [FILE | -e EXPR | -]
+       equa fmt [--check] [FILE...]";
 
-pub fn run(text: &str, context: &mut Context, path: &str) -> Result<Option<Value>, Error> {
-    let (tokens, poses) = lexer::lex(text, path)?;
-    if tokens.len() == 0 { return Ok(None) }
-    let node = parser::parse(tokens, poses, path)?;
-    let value = interpreter::get(&node, context, path)?;
-    Ok(Some(value))
-}
-
-fn main() {
-    let mut context = Context::new(); std_context(&mut context);
-    loop {
-        let mut input = String::new();
-        print!("> ");
-        let _ = io::stdout().flush();
-        io::stdin().read_line(&mut input).unwrap();
-        let res = run(input.as_str(), &mut context, "stdin");
-        if let Err(e) = res {
-            println!("{e}");
-            continue
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format { Text, Json, Latex, Unicode, MathML }
+impl Format {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            "latex" => Some(Self::Latex),
+            "unicode" => Some(Self::Unicode),
+            "mathml" => Some(Self::MathML),
+            _ => None
         }
-        if let Some(v) = res.unwrap() { println!("{v}"); }
     }
 }
 
-pub fn _sqrt(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).sqrt())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.sqrt())))
-        }
+pub fn display(value: &Value, format: Format) -> Result<String, Error> {
+    match format {
+        Format::Text => Ok(value.to_string()),
+        Format::Json => json::to_json(value),
+        Format::Latex => Ok(latex::value(value)),
+        Format::Unicode => Ok(unicode::pretty(value)),
+        Format::MathML => Ok(mathml::math(mathml::value(value))),
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
 }
-pub fn _floor(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(value.clone())
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.floor())))
-        }
+// an expression as written, json has no form for these so it falls back to the source format
+pub fn render(node: &parser::Node, text: &str, format: Format) -> String {
+    match format {
+        Format::Text | Format::Json => format::Formatter::new(text).node(node),
+        Format::Latex => latex::latex(node),
+        Format::Unicode => unicode::unicode(node),
+        Format::MathML => mathml::math(mathml::mathml(node)),
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
 }
-pub fn _ceil(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(value.clone())
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.ceil())))
-        }
-    }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
+
+fn main() {
+    std::process::exit(cli());
 }
-pub fn _round(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(value.clone())
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.round())))
-        }
+
+pub fn source(text: String) -> String {
+    if text.starts_with("#!") {
+        let end = text.find('\n').unwrap_or(text.len());
+        return " ".repeat(end) + &text[end..]
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
+    text
 }
-pub fn _abs(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Int(v.abs())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.abs())))
+
+// formats files in place, or stdin to stdout, with --check only reporting the files that would change
+fn fmt(args: impl Iterator<Item = String>) -> i32 {
+    let mut check = false;
+    let mut paths: Vec<String> = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-h" | "--help" => { println!("{USAGE}"); return 0 }
+            _ => paths.push(arg)
         }
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
-}
-pub fn _sin(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).sin())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.sin())))
+    if paths.is_empty() {
+        let mut text = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut text) { eprintln!("equa: stdin: {e}"); return 2 }
+        return match format::format(&text, "stdin") {
+            Ok(out) if check => if out == text { 0 } else { eprintln!("stdin is not formatted"); 1 }
+            Ok(out) => { print!("{out}"); 0 }
+            Err(e) => { eprintln!("{}", e.render("stdin", &text)); 2 }
         }
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
-}
-pub fn _cos(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).cos())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.cos())))
+    let mut code = 0;
+    for path in paths {
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => { eprintln!("equa: {path}: {e}"); code = 2; continue }
+        };
+        let out = match format::format(&text, &path) {
+            Ok(out) => out,
+            Err(e) => { eprintln!("{}", e.render(&path, &text)); code = 2; continue }
+        };
+        if out == text { continue }
+        if check {
+            println!("{path}");
+            code = code.max(1);
+        } else if let Err(e) = std::fs::write(&path, out) {
+            eprintln!("equa: {path}: {e}");
+            code = 2;
         }
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
+    code
 }
-pub fn _tan(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).tan())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.tan())))
+
+type Evaluate = fn(&str, &mut Context, &str) -> Result<Option<Value>, Error>;
+// what the command line asks for: the program and its path, how to print results and which evaluator runs them
+struct Options { program: Option<(String, String)>, format: Format, evaluate: Evaluate }
+
+// reads every argument, a usage error or --help gives the exit code instead
+fn options(mut args: impl Iterator<Item = String>, context: &mut Context) -> Result<Options, i32> {
+    let mut options = Options { program: None, format: Format::Text, evaluate: run };
+    let usage = |e: String| { eprintln!("equa: {e}\n{USAGE}"); 2 };
+    while let Some(arg) = args.next() {
+        let program = options.program.is_some();
+        match arg.as_str() {
+            "-h" | "--help" => { println!("{USAGE}"); return Err(0) }
+            "--max-depth" => match args.next().and_then(|x| x.parse::<usize>().ok()) {
+                Some(depth) => context.limits.max_depth = depth,
+                None => { eprintln!("{USAGE}"); return Err(2) }
+            }
+            "--fuel" => match args.next().and_then(|x| x.parse::<u64>().ok()) {
+                Some(fuel) => context.limits.fuel = Some(fuel),
+                None => { eprintln!("{USAGE}"); return Err(2) }
+            }
+            "--max-size" => match args.next().and_then(|x| x.parse::<usize>().ok()) {
+                Some(size) => context.limits.max_size = Some(size),
+                None => { eprintln!("{USAGE}"); return Err(2) }
+            }
+            "--timeout" => match args.next().and_then(|x| x.parse::<u64>().ok()) {
+                Some(ms) => context.limits.timeout = Some(std::time::Duration::from_millis(ms)),
+                None => { eprintln!("{USAGE}"); return Err(2) }
+            }
+            "--vm" => options.evaluate = vm::run,
+            "--json" => options.format = Format::Json,
+            "--latex" => options.format = Format::Latex,
+            "--unicode" => options.format = Format::Unicode,
+            "--mathml" => options.format = Format::MathML,
+            _ if program && (arg == "-e" || arg == "-" || !arg.starts_with('-')) => return Err(usage(format!("unexpected argument {arg}, only one program can be run"))),
+            "-e" => match args.next() {
+                Some(expr) => options.program = Some((expr, "<expr>".to_string())),
+                None => { eprintln!("{USAGE}"); return Err(2) }
+            }
+            "-" => {
+                let mut text = String::new();
+                if let Err(e) = io::stdin().read_to_string(&mut text) { eprintln!("equa: stdin: {e}"); return Err(2) }
+                options.program = Some((source(text), "stdin".to_string()));
+            }
+            flag if flag.starts_with('-') => return Err(usage(format!("unknown option {flag}"))),
+            path => match std::fs::read_to_string(path) {
+                Ok(text) => options.program = Some((source(text), path.to_string())),
+                Err(e) => { eprintln!("equa: {path}: {e}"); return Err(2) }
+            }
         }
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
+    Ok(options)
 }
-pub fn _asin(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).asin())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.asin())))
-        }
+
+fn cli() -> i32 {
+    if std::env::args().nth(1).as_deref() == Some("fmt") { return fmt(std::env::args().skip(2)) }
+    let mut context = Context::new(); builtins::std_context(&mut context).expect("a new context binds nothing");
+    // expressions, pipes and the prompt import relative to where equa was started
+    if let Ok(dir) = std::env::current_dir() { context.set_base(&dir) }
+    let Options { mut program, format, evaluate } = match options(std::env::args().skip(1), &mut context) {
+        Ok(options) => options,
+        Err(code) => return code
+    };
+    if program.is_none() && !io::stdin().is_terminal() {
+        let mut text = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut text) { eprintln!("equa: stdin: {e}"); return 2 }
+        program = Some((source(text), "stdin".to_string()));
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
-}
-pub fn _acos(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).acos())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.acos())))
+    match program {
+        Some((text, path)) => match evaluate(&text, &mut context, &path).and_then(|v| v.map(|v| display(&v, format)).transpose()) {
+            Ok(Some(v)) => { println!("{v}"); 0 }
+            Ok(None) => 0,
+            Err(e) => { eprintln!("{}", e.render(&path, &text)); 1 }
         }
+        None => { repl(&mut context, format); 0 }
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
 }
-pub fn _atan(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).atan())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.atan())))
-        }
-    }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
+
+pub fn history() -> Option<std::path::PathBuf> {
+    std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".equa_history"))
 }
-pub fn _atan2(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    let other = context.get(&"y".to_string()).unwrap();
-    if let (Value::Number(num), Value::Number(num2)) = (value, other) {
-        match (num, num2) {
-            (Number::Int(v1), Number::Int(v2)) => return Ok(Value::Number(Number::Float((*v1 as f64).atan2((*v1 as f64))))),
-            (Number::Float(v1), Number::Float(v2)) => return Ok(Value::Number(Number::Float(v1.atan2(*v2)))),
-            (Number::Int(v1), Number::Float(v2)) => return Ok(Value::Number(Number::Float((*v1 as f64).atan2(*v2)))),
-            (Number::Float(v1), Number::Int(v2)) => return Ok(Value::Number(Number::Float(v1.atan2((*v2 as f64))))),
+
+pub fn balanced(text: &str) -> bool {
+    let (tokens, _) = match lexer::lex(text, "stdin") {
+        Ok(res) => res,
+        Err(_) => return true
+    };
+    let mut depth: i64 = 0;
+    let mut abs = 0;
+    for token in tokens.iter() {
+        match token {
+            Token::EvalIn | Token::VectorIn | Token::SetIn => depth += 1,
+            Token::EvalOut | Token::VectorOut | Token::SetOut => depth -= 1,
+            Token::Abs => abs += 1,
+            _ => {}
         }
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
+    depth <= 0 && abs % 2 == 0
 }
-pub fn _sinh(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).sinh())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.sinh())))
-        }
+
+pub fn split_command(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((cmd, arg)) => (cmd, arg.trim()),
+        None => (line, "")
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
 }
-pub fn _cosh(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).cosh())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.cosh())))
-        }
-    }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
+
+// names an input of the prompt after its number and keeps its text, errors in functions it defined render against it later
+pub fn name(sources: &mut Vec<(String, String)>, text: &str) -> String {
+    let path = format!("stdin[{}]", sources.len() + 1);
+    sources.push((path.clone(), text.to_string()));
+    path
 }
-pub fn _tanh(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).tanh())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.tanh())))
-        }
+
+pub fn command(line: &str, path: &str, context: &mut Context, format: &mut Format) -> Result<(), Error> {
+    let (cmd, arg) = split_command(line);
+    match cmd {
+        ":vars" => {
+            let print = |name: &str, scope: &Scope| {
+                println!("-- {name} --");
+                for (id, value, constant) in scope.bindings() {
+                    println!("{id} {} {value}", if constant { "::" } else { ":=" });
+                }
+            };
+            print("global", context.global_scope());
+            print("answers", context.answers());
+            for (i, scope) in context.scopes().iter().enumerate() {
+                print(&format!("scope {i}"), scope);
+            }
+        }
+        ":type" => if let Some(v) = run(arg, &mut context.scratch(), path)? { println!("{}", v.typ()) }
+        ":ast" => {
+            let (tokens, poses) = lexer::lex(arg, path)?;
+            if tokens.is_empty() { return Ok(()) }
+            let (node, mut errors) = parser::parse_recover(tokens, poses, path);
+            println!("{node:#?}");
+            if errors.len() > 1 { return Err(Error::Many(errors)) }
+            if let Some(e) = errors.pop() { return Err(e) }
+        }
+        ":latex" => {
+            let (tokens, poses) = lexer::lex(arg, path)?;
+            if tokens.is_empty() { return Ok(()) }
+            println!("{}", latex::latex(&parser::parse(tokens, poses, path)?));
+        }
+        ":render" => {
+            let (tokens, poses) = lexer::lex(arg, path)?;
+            if tokens.is_empty() { return Ok(()) }
+            println!("{}", render(&parser::parse(tokens, poses, path)?, arg, *format));
+        }
+        ":mode" => match Format::parse(arg) {
+            Some(mode) => *format = mode,
+            None if arg.is_empty() => println!("{}", format!("{format:?}").to_lowercase()),
+            None => return Err(Error::UnknownMode(arg.to_string()))
+        }
+        ":tokens" => {
+            let (tokens, poses) = lexer::lex(arg, path)?;
+            for (token, pos) in tokens.iter().zip(poses.iter()) {
+                println!("{:?} {}..{}", token, pos.1.start, pos.1.end);
+            }
+        }
+        ":reset" => context.reset(),
+        ":load" => {
+            let text = match std::fs::read_to_string(arg) {
+                Ok(text) => source(text),
+                Err(e) => return Err(Error::Import(arg.to_string(), e.to_string()))
+            };
+            if let Some(v) = run(&text, context, arg)? { println!("{}", display(&v, *format)?) }
+        }
+        _ => return Err(Error::UnknownCommand(cmd.to_string()))
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
+    Ok(())
 }
-pub fn _asinh(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).asinh())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.asinh())))
+
+fn repl(context: &mut Context, mut format: Format) {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => { eprintln!("equa: {e}"); return }
+    };
+    let history = history();
+    if let Some(path) = &history { let _ = editor.load_history(path); }
+    let mut input = String::new();
+    let mut sources: Vec<(String, String)> = vec![];
+    loop {
+        let line = match editor.readline(if input.is_empty() { "> " } else { ". " }) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => { input.clear(); continue }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => { eprintln!("equa: {e}"); break }
+        };
+        if input.is_empty() && line.trim_start().starts_with(':') {
+            let _ = editor.add_history_entry(line.trim());
+            if let Some(path) = &history { let _ = editor.save_history(path); }
+            let path = name(&mut sources, split_command(line.trim()).1);
+            if let Err(e) = command(line.trim(), &path, context, &mut format) { println!("{}", e.render_sources(&sources)) }
+            continue
         }
-    }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
-}
-pub fn _acosh(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).acosh())))
+        input.push_str(&line);
+        input.push('\n');
+        if !balanced(&input) { continue }
+        let _ = editor.add_history_entry(input.trim_end());
+        if let Some(path) = &history { let _ = editor.save_history(path); }
+        let path = name(&mut sources, &input);
+        let res = run(input.as_str(), context, &path);
+        if let Err(e) = res {
+            println!("{}", e.render_sources(&sources));
+            input.clear();
+            continue
         }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.acosh())))
+        input.clear();
+        if let Some(v) = res.unwrap() {
+            context.answer(&v);
+            match display(&v, format) {
+                Ok(s) => println!("{s}"),
+                Err(e) => println!("{e}"),
+            }
         }
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
 }
-pub fn _atanh(context: &mut Context) -> Result<Value, Error> {
-    let value = context.get(&"x".to_string()).unwrap();
-    if let Value::Number(num) = value {
-        if let Number::Int(v) = num {
-            return Ok(Value::Number(Number::Float((*v as f64).atanh())))
-        }
-        if let Number::Float(v) = num {
-            return Ok(Value::Number(Number::Float(v.atanh())))
-        }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shebang_lines_are_blanked() {
+        let text = source("#!/usr/bin/env equa\n1 + 2".to_string());
+        assert_eq!(text, format!("{}\n1 + 2", " ".repeat(19)));
+        assert_eq!(run(&text, &mut Context::new(), "script.eq").unwrap().unwrap().to_string(), "3");
+        assert_eq!(source("1 #!".to_string()), "1 #!");
+    }
+    #[test]
+    fn errors_after_a_shebang_keep_their_line() {
+        let text = source("#!/usr/bin/env equa\nx".to_string());
+        let e = run(&text, &mut Context::new(), "script.eq").unwrap_err();
+        assert!(e.render("script.eq", &text).contains("--> script.eq:2:1"));
+    }
+    fn parsed(args: &[&str]) -> Result<Options, i32> {
+        options(args.iter().map(|x| x.to_string()), &mut Context::new())
+    }
+    #[test]
+    fn options_after_the_program_apply() {
+        let parsed = parsed(&["-e", "{1 2}", "--json"]).unwrap();
+        assert_eq!(parsed.program, Some(("{1 2}".to_string(), "<expr>".to_string())));
+        assert_eq!(parsed.format, Format::Json);
+        let mut context = Context::new();
+        options(["-e", "1", "--fuel", "5"].iter().map(|x| x.to_string()), &mut context).unwrap();
+        assert_eq!(context.limits.fuel, Some(5));
+    }
+    #[test]
+    fn extra_arguments_are_usage_errors() {
+        assert_eq!(parsed(&["-e", "1", "--bogus"]).err(), Some(2));
+        assert_eq!(parsed(&["--bogus"]).err(), Some(2));
+        assert_eq!(parsed(&["-e", "1", "-e", "2"]).err(), Some(2));
+        assert_eq!(parsed(&["-e", "1", "script.eq"]).err(), Some(2));
+        assert_eq!(parsed(&["-e"]).err(), Some(2));
+    }
+    #[test]
+    fn input_continues_until_brackets_close() {
+        assert!(balanced("f(1, 2)"));
+        assert!(!balanced("f := x -> ("));
+        assert!(!balanced("[1 2\n{3"));
+        assert!(balanced("[1 2\n{3}]"));
+        assert!(!balanced("|x"));
+        assert!(balanced("|x| + 1"));
+        // a stray closing bracket is left for the parser to report
+        assert!(balanced("1)"));
+    }
+    #[test]
+    fn type_leaves_the_context_alone() {
+        let mut context = Context::new();
+        context.limits.fuel = Some(1000);
+        run("y := 1", &mut context, "stdin").unwrap();
+        command(":type x := [y]", "stdin", &mut context, &mut Format::Text).unwrap();
+        command(":type y := 2", "stdin", &mut context, &mut Format::Text).unwrap();
+        assert!(context.get(&"x".to_string()).is_none());
+        assert_eq!(context.get(&"y".to_string()).unwrap().to_string(), "1");
+        assert_eq!(context.steps(), 2);
+        assert!(context.answers().bindings().is_empty());
+    }
+    #[test]
+    fn reset_and_unknown_commands() {
+        let mut context = Context::new();
+        builtins::std_context(&mut context).unwrap();
+        run("y := 1", &mut context, "stdin").unwrap();
+        command(":reset", "stdin", &mut context, &mut Format::Text).unwrap();
+        assert!(context.get(&"y".to_string()).is_none());
+        assert!(context.get(&"sqrt".to_string()).is_some());
+        assert_eq!(command(":frobnicate", "stdin", &mut context, &mut Format::Text), Err(Error::UnknownCommand(":frobnicate".to_string())));
+        assert_eq!(split_command(":type  x + 1 "), (":type", "x + 1"));
+    }
+    #[test]
+    fn modes_switch_the_output() {
+        assert_eq!(Format::parse("mathml"), Some(Format::MathML));
+        assert_eq!(Format::parse("MathML"), None);
+        let mut context = Context::new();
+        let mut format = Format::Text;
+        command(":mode unicode", "stdin", &mut context, &mut format).unwrap();
+        assert_eq!(format, Format::Unicode);
+        assert_eq!(command(":mode braille", "stdin", &mut context, &mut format), Err(Error::UnknownMode("braille".to_string())));
+        assert_eq!(format, Format::Unicode);
+        let v = Value::Number(Number::Float(-0.5));
+        assert_eq!(display(&v, Format::Unicode).unwrap(), "−0.5");
+        assert_eq!(display(&v, Format::Latex).unwrap(), "-0.5");
+        assert_eq!(display(&v, Format::Json).unwrap(), "-0.5");
+    }
+    #[test]
+    fn errors_render_against_the_input_that_defined_the_function() {
+        let mut context = Context::new();
+        builtins::std_context(&mut context).unwrap();
+        let mut sources: Vec<(String, String)> = vec![];
+        let path = name(&mut sources, "f := x -> 0 + 0 + y\n");
+        run(&sources[0].1.clone(), &mut context, &path).unwrap();
+        let path = name(&mut sources, "id∘id∘id∘id∘f(1)\n");
+        let e = run(&sources[1].1.clone(), &mut context, &path).unwrap_err();
+        let text = e.render_sources(&sources);
+        assert!(text.starts_with("ERROR: y is not defined\n --> stdin[1]:1:19\n  |\n1 | f := x -> 0 + 0 + y\n  |                   ^"), "{text}");
+        assert!(text.contains("in f(1) at stdin[2]:1:"), "{text}");
     }
-    Err(Error::ExpectedType(value.typ(), Type::Number))
 }
-pub fn std_context(context: &mut Context) {
-    context.global_const(&"sqrt".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _sqrt));
-    context.global_const(&"floor".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _floor));
-    context.global_const(&"ceil".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _ceil));
-    context.global_const(&"round".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _round));
-    context.global_const(&"abs".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _abs));
-    context.global_const(&"sin".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _sin));
-    context.global_const(&"cos".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _cos));
-    context.global_const(&"tan".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _tan));
-    context.global_const(&"asin".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _asin));
-    context.global_const(&"acos".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _acos));
-    context.global_const(&"atan".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _atan));
-    context.global_const(&"atan2".to_string(), &Value::ForeignFunction(vec!["x".to_string(), "y".to_string()], _atan2));
-    context.global_const(&"sin".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _sinh));
-    context.global_const(&"cosh".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _cosh));
-    context.global_const(&"tanh".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _tanh));
-    context.global_const(&"asinh".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _asinh));
-    context.global_const(&"acosh".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _acosh));
-    context.global_const(&"atanh".to_string(), &Value::ForeignFunction(vec!["x".to_string()], _atanh));
-    context.global_const(&"PI".to_string(), &Value::Number(Number::Float(std::f64::consts::PI)));
-    context.global_const(&"TAU".to_string(), &Value::Number(Number::Float(std::f64::consts::TAU)));
-    context.global_const(&"E".to_string(), &Value::Number(Number::Float(std::f64::consts::E)));
-    context.global_const(&"inf".to_string(), &Value::Number(Number::Float(std::f64::INFINITY)));
-    context.global_const(&"NaN".to_string(), &Value::Number(Number::Float(std::f64::NAN)));
-}
\ No newline at end of file
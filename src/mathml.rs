@@ -0,0 +1,247 @@
+use crate::value::*;
+use crate::lexer::*;
+use crate::parser::*;
+use crate::format::precedence;
+use crate::latex::lambda;
+use crate::unicode;
+
+const FUNCTIONS: [&str; 12] = ["sin", "cos", "tan", "asin", "acos", "atan", "sinh", "cosh", "tanh", "asinh", "acosh", "atanh"];
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+fn mo(s: &str) -> String { format!("<mo>{}</mo>", escape(s)) }
+fn mi(s: &str) -> String { format!("<mi>{}</mi>", escape(s)) }
+fn mn(s: &str) -> String { format!("<mn>{}</mn>", escape(s)) }
+fn mrow(items: &[String]) -> String { format!("<mrow>{}</mrow>", items.concat()) }
+fn fenced(open: &str, inner: String, close: &str) -> String { mrow(&[mo(open), inner, mo(close)]) }
+fn joined(items: Vec<String>, sep: &str) -> String {
+    let mut row: Vec<String> = vec![];
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 { row.push(mo(sep)) }
+        row.push(item);
+    }
+    mrow(&row)
+}
+fn separated(items: Vec<String>) -> String { joined(items, ",") }
+fn table(rows: Vec<Vec<String>>) -> String {
+    let rows = rows.into_iter().map(|x| format!("<mtr>{}</mtr>", x.into_iter().map(|x| format!("<mtd>{x}</mtd>")).collect::<String>()));
+    format!("<mtable>{}</mtable>", rows.collect::<String>())
+}
+
+pub fn word(v: &str) -> String {
+    if let Some((name, index)) = v.split_once('_') {
+        if !name.is_empty() && !index.is_empty() {
+            let index = if index.chars().all(|c| c.is_ascii_digit()) { mn(index) } else { word(index) };
+            return format!("<msub>{}{index}</msub>", word(name))
+        }
+    }
+    mi(&unicode::word(v))
+}
+fn float(v: f64) -> String {
+    if v.is_nan() { return mi("NaN") }
+    if v.is_infinite() { return if v > 0.0 { mi("∞") } else { mrow(&[mo("−"), mi("∞")]) } }
+    if v < 0.0 { return mrow(&[mo("−"), mn(&(-v).to_string())]) }
+    mn(&v.to_string())
+}
+
+fn operand(node: &Node, min: usize) -> String {
+    if precedence(node) < min { return fenced("(", mathml(node), ")") }
+    mathml(node)
+}
+fn call(v: &Node, args: &[Node], partial: bool) -> String {
+    if let (Node::Word { v: name, .. }, false) = (v, partial) {
+        match (name.as_str(), args) {
+            ("sqrt", [x]) => return format!("<msqrt>{}</msqrt>", mathml(x)),
+            ("abs", [x]) => return fenced("|", mathml(x), "|"),
+            ("floor", [x]) => return fenced("⌊", mathml(x), "⌋"),
+            ("ceil", [x]) => return fenced("⌈", mathml(x), "⌉"),
+            ("filter", [p, s]) => if let Some((x, body)) = lambda(p) {
+                return fenced("{", mrow(&[word(x), mo("∈"), mathml(s), mo("|"), mathml(body)]), "}")
+            }
+            ("map", [f, s]) => if let Some((x, body)) = lambda(f) {
+                return fenced("{", mrow(&[mathml(body), mo("|"), word(x), mo("∈"), mathml(s)]), "}")
+            }
+            ("integrate", [f, a, b]) => if let Some((x, body)) = lambda(f) {
+                let integral = format!("<msubsup>{}{}{}</msubsup>", mo("∫"), mathml(a), mathml(b));
+                return mrow(&[integral, mathml(body), mo("\u{2062}"), mi("d"), word(x)])
+            }
+            _ => {}
+        }
+    }
+    let mut args = args.iter().map(mathml).collect::<Vec<String>>();
+    if partial { args.push(mo("…")) }
+    let callee = match v {
+        Node::Word { v, .. } if FUNCTIONS.contains(&v.as_str()) => mi(v),
+        v => operand(v, 12),
+    };
+    mrow(&[callee, mo("\u{2061}"), fenced("(", separated(args), ")")])
+}
+
+/// Renders an expression as presentation markup, without the surrounding math element.
+pub fn mathml(node: &Node) -> String {
+    match node {
+        Node::Int { v, .. } => mn(&v.to_string()),
+        Node::Float { v, .. } => float(*v),
+        Node::Word { v, .. } => word(v),
+        Node::Vector { v, .. } => {
+            let rows = v.iter().map(|x| match x { Node::Vector { v, .. } => Some(v.iter().map(mathml).collect()), _ => None });
+            match rows.collect::<Option<Vec<Vec<String>>>>() {
+                Some(rows) if !rows.is_empty() => fenced("[", table(rows), "]"),
+                _ => fenced("[", separated(v.iter().map(mathml).collect()), "]"),
+            }
+        }
+        Node::Set { v, .. } if v.values.is_empty() => mi("∅"),
+        Node::Set { v, .. } => fenced("{", separated(v.values.iter().map(mathml).collect()), "}"),
+        Node::Tuple { nodes, .. } => fenced("(", separated(nodes.iter().map(mathml).collect()), ")"),
+        Node::Binary { op: Token::Slash, left, right, .. } => format!("<mfrac>{}{}</mfrac>", mathml(left), mathml(right)),
+        Node::Binary { op: Token::Exponent, left, right, .. } => {
+            let base = match left.as_ref() {
+                Node::Binary { op: Token::Slash, .. } => fenced("(", mathml(left), ")"),
+                left => operand(left, 12),
+            };
+            format!("<msup>{base}{}</msup>", mathml(right))
+        }
+        Node::Binary { op: Token::Field, left, right, .. } => mrow(&[operand(left, 13), mo("."), operand(right, 14)]),
+        Node::Binary { op, left, right, .. } => {
+            let symbol = match op {
+                Token::Minus => "−".to_string(),
+                Token::Star => "·".to_string(),
+                Token::PlusMinus => "±".to_string(),
+                Token::NotEqual => "≠".to_string(),
+                Token::LessEqual => "≤".to_string(),
+                Token::GreaterEqual => "≥".to_string(),
+                Token::Remove => "∖".to_string(),
+                op => op.to_string(),
+            };
+            // fractions stand apart on their own, the other operands follow the precedence
+            let side = |node: &Node, min: usize| match node {
+                Node::Binary { op: Token::Slash, .. } => mathml(node),
+                node => operand(node, min),
+            };
+            let prec = precedence(node);
+            mrow(&[side(left, prec), mo(&symbol), side(right, prec + 1)])
+        }
+        Node::Unary { op: Token::Minus, node, .. } => mrow(&[mo("−"), operand(node, 10)]),
+        Node::Unary { op: Token::Amount, node, .. } => mrow(&[mo("#"), operand(node, 12)]),
+        Node::Unary { node, .. } => mrow(&[mo("…"), operand(node, 14)]),
+        Node::Percent { node, .. } => mrow(&[operand(node, 10), mo("%")]),
+        Node::Abs { node, .. } => fenced("|", mathml(node), "|"),
+        Node::Call { v, args, partial, .. } => call(v, args, *partial),
+        Node::Assign { m: false, id, expr, .. } => match expr.as_ref() {
+            Node::Function { id: params, body, .. } => {
+                let params = match params.as_ref() { Node::Tuple { nodes, .. } => nodes.iter().map(mathml).collect(), params => vec![mathml(params)] };
+                mrow(&[mathml(id), mo("\u{2061}"), fenced("(", separated(params), ")"), mo("="), mathml(body)])
+            }
+            expr => mrow(&[mathml(id), mo("="), mathml(expr)]),
+        }
+        Node::Assign { m: true, id, expr, .. } => mrow(&[mathml(id), mo("≔"), operand(expr, 1)]),
+        Node::Function { id, body, .. } => mrow(&[operand(id, 3), mo("↦"), mathml(body)]),
+        Node::Condition { .. } => {
+            let mut rows: Vec<Vec<String>> = vec![];
+            let mut node = node;
+            while let Node::Condition { cond, case, default, .. } = node {
+                rows.push(vec![mathml(case), mrow(&["<mtext>if&#xA0;</mtext>".to_string(), mathml(cond)])]);
+                node = default;
+            }
+            rows.push(vec![mathml(node), "<mtext>otherwise</mtext>".to_string()]);
+            mrow(&[mo("{"), table(rows)])
+        }
+        Node::Import { path, .. } => format!("<mtext>import \"{}\"</mtext>", escape(path)),
+        Node::Block { nodes, .. } => joined(nodes.iter().map(mathml).collect(), ";"),
+        Node::Named { id, expr, .. } => mrow(&[word(id), mo(":"), mathml(expr)]),
+        Node::Error { .. } => "<merror><mtext>?</mtext></merror>".to_string(),
+    }
+}
+
+fn param(param: &Param) -> String {
+    match (&param.default, param.rest) {
+        (_, true) => mrow(&[mo("…"), word(&param.id)]),
+        (Some(default), _) => mrow(&[word(&param.id), mo("≔"), mathml(default)]),
+        (None, _) => word(&param.id),
+    }
+}
+pub fn value(value: &Value) -> String {
+    let list = |values: &[Value]| separated(values.iter().map(self::value).collect());
+    match value {
+        Value::Number(Number::Int(v)) if *v < 0 => mrow(&[mo("−"), mn(&v.unsigned_abs().to_string())]),
+        Value::Number(Number::Int(v)) => mn(&v.to_string()),
+        Value::Number(Number::Float(v)) => float(*v),
+        Value::Vector(values) => {
+            let rows = values.iter().map(|x| match x { Value::Vector(v) => Some(v.iter().map(self::value).collect()), _ => None });
+            match rows.collect::<Option<Vec<Vec<String>>>>() {
+                Some(rows) if !rows.is_empty() => fenced("[", table(rows), "]"),
+                _ => fenced("[", list(values), "]"),
+            }
+        }
+        Value::Set(values) if values.is_empty() => mi("∅"),
+        Value::Set(values) => fenced("{", list(&values.values), "}"),
+        Value::Tuple(values) => fenced("(", list(values), ")"),
+        Value::Function(params, body) => match params.as_slice() {
+            [p] if p.default.is_none() && !p.rest => mrow(&[word(&p.id), mo("↦"), mathml(body)]),
+            params => mrow(&[fenced("(", separated(params.iter().map(param).collect()), ")"), mo("↦"), mathml(body)]),
+        }
+        Value::ForeignFunction(params, _) => mrow(&["<mtext>foreign-function</mtext>".to_string(),
+            fenced("(", separated(params.iter().map(|x| word(&x.id)).collect()), ")")]),
+        Value::Composed(func, other) => mrow(&[fenced("(", self::value(func), ")"), mo("∘"), fenced("(", self::value(other), ")")]),
+        Value::Memo(func, _) | Value::Scoped(func, _) => self::value(func),
+        Value::Compiled(proto) => self::value(&proto.function),
+        Value::Partial(func, args, named) => {
+            let mut args = args.iter().map(self::value).collect::<Vec<String>>();
+            args.extend(named.iter().map(|(id, x)| mrow(&[word(id), mo(":"), self::value(x)])));
+            args.push(mo("…"));
+            mrow(&[fenced("(", self::value(func), ")"), fenced("(", separated(args), ")")])
+        }
+        Value::Module(path, _) => format!("<mtext>module {}</mtext>", escape(path)),
+        Value::Str(v) => format!("<mtext>{}</mtext>", escape(v)),
+    }
+}
+/// Wraps rendered markup in a math element.
+pub fn math(inner: String) -> String {
+    format!("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">{inner}</math>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    fn render(text: &str) -> String {
+        let (tokens, poses) = lex(text, "test").unwrap();
+        mathml(&parse(tokens, poses, "test").unwrap())
+    }
+    fn shown(text: &str) -> String {
+        value(&Engine::new().eval(text).unwrap().unwrap())
+    }
+    #[test]
+    fn expressions() {
+        assert_eq!(render("x^2 + y^(n+1)"), "<mrow><msup><mi>x</mi><mn>2</mn></msup><mo>+</mo><msup><mi>y</mi><mrow><mi>n</mi><mo>+</mo><mn>1</mn></mrow></msup></mrow>");
+        assert_eq!(render("1/2"), "<mfrac><mn>1</mn><mn>2</mn></mfrac>");
+        assert_eq!(render("sqrt(x + 1)"), "<msqrt><mrow><mi>x</mi><mo>+</mo><mn>1</mn></mrow></msqrt>");
+        assert_eq!(render("alpha_1 * theta"), "<mrow><msub><mi>α</mi><mn>1</mn></msub><mo>·</mo><mi>θ</mi></mrow>");
+        assert_eq!(render("x_max"), "<msub><mi>x</mi><mi>max</mi></msub>");
+        assert_eq!(render("a * (b + c)"), "<mrow><mi>a</mi><mo>·</mo><mrow><mo>(</mo><mrow><mi>b</mi><mo>+</mo><mi>c</mi></mrow><mo>)</mo></mrow></mrow>");
+        assert_eq!(render("-3 - x"), "<mrow><mrow><mo>−</mo><mn>3</mn></mrow><mo>−</mo><mi>x</mi></mrow>");
+        assert_eq!(render("a <= b"), "<mrow><mi>a</mi><mo>≤</mo><mi>b</mi></mrow>");
+        assert_eq!(render("f(1, ..)"), "<mrow><mi>f</mi><mo>\u{2061}</mo><mrow><mo>(</mo><mrow><mn>1</mn><mo>,</mo><mo>…</mo></mrow><mo>)</mo></mrow></mrow>");
+    }
+    #[test]
+    fn markup_is_escaped() {
+        assert_eq!(render("x < 0 ? -x : x"), "<mrow><mo>{</mo><mtable><mtr><mtd><mrow><mo>−</mo><mi>x</mi></mrow></mtd><mtd><mrow><mtext>if&#xA0;</mtext>\
+            <mrow><mi>x</mi><mo>&lt;</mo><mn>0</mn></mrow></mrow></mtd></mtr><mtr><mtd><mi>x</mi></mtd><mtd><mtext>otherwise</mtext></mtd></mtr></mtable></mrow>");
+        assert_eq!(render("filter(x -> x > 2, s)"), "<mrow><mo>{</mo><mrow><mi>x</mi><mo>∈</mo><mi>s</mi><mo>|</mo><mrow><mi>x</mi><mo>&gt;</mo><mn>2</mn></mrow></mrow><mo>}</mo></mrow>");
+    }
+    #[test]
+    fn integrals_and_functions() {
+        assert_eq!(render("integrate(x -> x^2, 0, 1)"), "<mrow><msubsup><mo>∫</mo><mn>0</mn><mn>1</mn></msubsup><msup><mi>x</mi><mn>2</mn></msup><mo>\u{2062}</mo><mi>d</mi><mi>x</mi></mrow>");
+        assert_eq!(render("x -> x"), "<mrow><mi>x</mi><mo>↦</mo><mi>x</mi></mrow>");
+    }
+    #[test]
+    fn values() {
+        assert_eq!(shown("[[1 2] [3 4]]"), "<mrow><mo>[</mo><mtable><mtr><mtd><mn>1</mn></mtd><mtd><mn>2</mn></mtd></mtr><mtr><mtd><mn>3</mn></mtd><mtd><mn>4</mn></mtd></mtr></mtable><mo>]</mo></mrow>");
+        assert_eq!(shown("{}"), "<mi>∅</mi>");
+        assert_eq!(shown("-2.5"), "<mrow><mo>−</mo><mn>2.5</mn></mrow>");
+        assert_eq!(shown("inf"), "<mi>∞</mi>");
+        assert_eq!(math(shown("1")), "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mn>1</mn></math>");
+    }
+}
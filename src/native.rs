@@ -0,0 +1,177 @@
+use crate::set::*;
+use crate::value::*;
+use crate::error::*;
+use crate::interpreter::*;
+use std::rc::Rc;
+
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, Error>;
+    // the value of an optional argument that wasn't passed
+    fn missing() -> Option<Self> { None }
+}
+pub trait IntoValue {
+    fn into_value(self) -> Result<Value, Error>;
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, Error> { Ok(value.clone()) }
+}
+impl FromValue for Number {
+    fn from_value(value: &Value) -> Result<Self, Error> {
+        match value {
+            Value::Number(num) => Ok(num.clone()),
+            _ => Err(Error::ExpectedType(Type::Number, value.typ()))
+        }
+    }
+}
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self, Error> { Ok(Number::from_value(value)?.to_float()) }
+}
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self, Error> {
+        match Number::from_value(value)? {
+            Number::Int(v) => Ok(v),
+            Number::Float(v) if v.fract() == 0.0 && v.abs() < i64::MAX as f64 => Ok(v as i64),
+            Number::Float(_) => Err(Error::ExpectedInt(value.clone()))
+        }
+    }
+}
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, Error> { Ok(value.truthy()) }
+}
+impl FromValue for Vec<Value> {
+    fn from_value(value: &Value) -> Result<Self, Error> {
+        match value {
+            Value::Vector(values) | Value::Tuple(values) => Ok(values.to_vec()),
+            Value::Set(values) => Ok(values.values.clone()),
+            _ => Err(Error::ExpectedTypes(vec![Type::Vector, Type::Set, Type::Tuple], value.typ()))
+        }
+    }
+}
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, Error> { Ok(Some(T::from_value(value)?)) }
+    fn missing() -> Option<Self> { Some(None) }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Result<Value, Error> { Ok(self) }
+}
+impl IntoValue for Number {
+    fn into_value(self) -> Result<Value, Error> { Ok(Value::Number(self)) }
+}
+impl IntoValue for f64 {
+    fn into_value(self) -> Result<Value, Error> { Ok(Value::Number(Number::Float(self))) }
+}
+impl IntoValue for i64 {
+    fn into_value(self) -> Result<Value, Error> { Ok(Value::Number(Number::Int(self))) }
+}
+impl IntoValue for bool {
+    fn into_value(self) -> Result<Value, Error> { Ok(Value::Number(Number::Int(self as i64))) }
+}
+impl IntoValue for Vec<Value> {
+    fn into_value(self) -> Result<Value, Error> { Ok(Value::Vector(self.into())) }
+}
+impl IntoValue for Set<Value> {
+    fn into_value(self) -> Result<Value, Error> { Ok(Value::Set(self.into())) }
+}
+impl<T: IntoValue> IntoValue for Result<T, Error> {
+    fn into_value(self) -> Result<Value, Error> { self?.into_value() }
+}
+
+pub fn arg<T: FromValue>(args: &[Value], idx: usize) -> Result<T, Error> {
+    match args.get(idx) {
+        Some(value) => T::from_value(value),
+        None => T::missing().ok_or(Error::Arity(idx + 1, None, args.len()))
+    }
+}
+
+/// Rust closures whose arguments and result convert to and from values.
+pub trait IntoNative<Args> {
+    fn into_native(self) -> ForeignFunction;
+}
+macro_rules! into_native {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoNative<($($arg,)*)> for F
+        where F: Fn($($arg),*) -> R + 'static, R: IntoValue, $($arg: FromValue),* {
+            #[allow(non_snake_case, unused_mut, unused_variables, unused_assignments)]
+            fn into_native(self) -> ForeignFunction {
+                Rc::new(move |args, _, _| {
+                    let mut idx = 0;
+                    $(let $arg = arg::<$arg>(args, idx)?; idx += 1;)*
+                    self($($arg),*).into_value()
+                })
+            }
+        }
+    };
+}
+into_native!();
+into_native!(A);
+into_native!(A, B);
+into_native!(A, B, C);
+into_native!(A, B, C, D);
+
+/// A native function that gets the raw arguments, the calling context and the path of the caller.
+pub fn native<P: Into<Param> + Clone>(params: &[P], function: impl Fn(&[Value], &mut Context, &str) -> Result<Value, Error> + 'static) -> Value {
+    Value::ForeignFunction(Rc::new(params.iter().cloned().map(Into::into).collect()), Rc::new(function))
+}
+pub fn typed<Args, P: Into<Param> + Clone>(params: &[P], function: impl IntoNative<Args>) -> Value {
+    Value::ForeignFunction(Rc::new(params.iter().cloned().map(Into::into).collect()), function.into_native())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    fn int(v: i64) -> Value { Value::Number(Number::Int(v)) }
+    fn float(v: f64) -> Value { Value::Number(Number::Float(v)) }
+
+    #[test]
+    fn arguments_convert_from_values() {
+        assert_eq!(i64::from_value(&float(3.0)), Ok(3));
+        assert_eq!(i64::from_value(&float(3.5)), Err(Error::ExpectedInt(float(3.5))));
+        assert_eq!(f64::from_value(&int(2)), Ok(2.0));
+        assert_eq!(bool::from_value(&int(0)), Ok(false));
+        let tuple = Value::Tuple(vec![int(1), int(2)].into());
+        assert_eq!(Vec::<Value>::from_value(&tuple), Ok(vec![int(1), int(2)]));
+        assert_eq!(f64::from_value(&tuple), Err(Error::ExpectedType(Type::Number, Type::Tuple)));
+        assert_eq!(arg::<Option<i64>>(&[int(1)], 1), Ok(None));
+        assert_eq!(arg::<i64>(&[int(1)], 1), Err(Error::Arity(2, None, 1)));
+    }
+    #[test]
+    fn results_convert_into_values() {
+        assert_eq!(true.into_value(), Ok(int(1)));
+        assert_eq!(2.5.into_value(), Ok(float(2.5)));
+        assert_eq!(vec![int(1)].into_value().unwrap().to_string(), "[1]");
+        assert_eq!(Err::<i64, Error>(Error::Tolerance(0.0)).into_value(), Err(Error::Tolerance(0.0)));
+    }
+    #[test]
+    fn typed_closures_are_callable() {
+        let mut engine = Engine::new();
+        engine.register("clamp", &["x", "lo", "hi"], |x: f64, lo: f64, hi: f64| x.max(lo).min(hi)).unwrap();
+        engine.register("pick", &[Param::from("v"), Param::optional("i")], |v: Vec<Value>, i: Option<i64>| v[i.unwrap_or(0) as usize].clone()).unwrap();
+        assert_eq!(engine.eval("clamp(5, 0, 2)").unwrap(), Some(float(2.0)));
+        assert_eq!(engine.eval("clamp(hi: 2, x: -1, lo: 0)").unwrap(), Some(float(0.0)));
+        assert_eq!(engine.eval("pick([4 5 6])").unwrap(), Some(int(4)));
+        assert_eq!(engine.eval("pick([4 5 6], 2)").unwrap(), Some(int(6)));
+        let e = engine.eval("clamp([1], 0, 2)").unwrap_err().to_string();
+        assert_eq!(e.lines().next(), Some("ERROR: expected type number, got vector"));
+    }
+    #[test]
+    fn named_arguments_cant_skip_optional_ones() {
+        let mut engine = Engine::new();
+        let params = [Param::from("a"), Param::optional("b"), Param::optional("c")];
+        engine.register_native("f", &params, |args, _, _| Ok(Value::Tuple(args.to_vec().into()))).unwrap();
+        assert_eq!(engine.eval("f(1, b: 2, c: 3)").unwrap().unwrap().to_string(), "(1, 2, 3)");
+        assert_eq!(engine.eval("f(1, b: 2)").unwrap().unwrap().to_string(), "(1, 2)");
+        let e = engine.eval("f(1, c: 3)").unwrap_err().to_string();
+        assert_eq!(e.lines().next(), Some("ERROR: argument c is given but b before it is not"));
+    }
+    #[test]
+    fn builtins_are_typed() {
+        let mut engine = Engine::new();
+        assert_eq!(engine.eval("atan2(1, 1)").unwrap(), Some(float(1f64.atan2(1.0))));
+        assert_eq!(engine.eval("sinh(1)").unwrap(), Some(float(1f64.sinh())));
+        assert_eq!(engine.eval("sin(1)").unwrap(), Some(float(1f64.sin())));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::error::*;
+use crate::value::*;
+use crate::interpreter::*;
+
+pub const TOLERANCE: f64 = 1e-12;
+pub const MAX_ITER: usize = 200;
+pub const MAX_DEPTH: usize = 50;
+const OPEN: f64 = 1e-12;
+
+pub fn function<'a>(func: &'a Value, context: &'a mut Context, path: &'a str) -> impl FnMut(f64) -> Result<f64, Error> + 'a {
+    move |x| match call(func, vec![Value::Number(Number::Float(x))], context, path)? {
+        Value::Number(v) => Ok(v.to_float()),
+        value => Err(Error::ExpectedType(Type::Number, value.typ()))
+    }
+}
+
+pub fn derivative(f: &mut dyn FnMut(f64) -> Result<f64, Error>, x: f64) -> Result<f64, Error> {
+    let h = 1e-7 * x.abs().max(1.0);
+    Ok((f(x + h)? - f(x - h)?) / (2.0 * h))
+}
+
+pub fn newton(f: &mut dyn FnMut(f64) -> Result<f64, Error>, x0: f64, tol: f64) -> Result<f64, Error> {
+    let mut x = x0;
+    for _ in 0..MAX_ITER {
+        let y = f(x)?;
+        if y == 0.0 { return Ok(x) }
+        let dy = derivative(f, x)?;
+        if dy == 0.0 || !dy.is_finite() { break }
+        let next = x - y / dy;
+        if !next.is_finite() { break }
+        if (next - x).abs() <= tol * next.abs().max(1.0) { return Ok(next) }
+        x = next;
+    }
+    Err(Error::NoConvergence("newton".to_string(), MAX_ITER))
+}
+
+pub fn brent(f: &mut dyn FnMut(f64) -> Result<f64, Error>, a: f64, b: f64, tol: f64) -> Result<f64, Error> {
+    let (mut a, mut b) = (a, b);
+    let (mut fa, mut fb) = (f(a)?, f(b)?);
+    if fa == 0.0 { return Ok(a) }
+    if fb == 0.0 { return Ok(b) }
+    if fa.signum() == fb.signum() { return Err(Error::NoSignChange(a, b)) }
+    if fa.abs() < fb.abs() { std::mem::swap(&mut a, &mut b); std::mem::swap(&mut fa, &mut fb); }
+    let (mut c, mut fc) = (a, fa);
+    let mut d = b - a;
+    let mut bisected = true;
+    for _ in 0..MAX_ITER {
+        if fb == 0.0 || (b - a).abs() <= tol * b.abs().max(1.0) { return Ok(b) }
+        let mut s = if fa != fc && fb != fc {
+            a * fb * fc / ((fa - fb) * (fa - fc))
+            + b * fa * fc / ((fb - fa) * (fb - fc))
+            + c * fa * fb / ((fc - fa) * (fc - fb))
+        } else {
+            b - fb * (b - a) / (fb - fa)
+        };
+        let bound = (3.0 * a + b) / 4.0;
+        // the interpolation has to land between the bound and b and shrink the step faster than bisection
+        let last = if bisected { (b - c).abs() } else { (c - d).abs() };
+        if !(s > bound.min(b) && s < bound.max(b)) || (s - b).abs() >= last / 2.0 {
+            s = (a + b) / 2.0;
+            bisected = true;
+        } else {
+            bisected = false;
+        }
+        let fs = f(s)?;
+        d = c;
+        c = b; fc = fb;
+        if fa.signum() == fs.signum() { a = s; fa = fs; } else { b = s; fb = fs; }
+        if fa.abs() < fb.abs() { std::mem::swap(&mut a, &mut b); std::mem::swap(&mut fa, &mut fb); }
+    }
+    Err(Error::NoConvergence("brent".to_string(), MAX_ITER))
+}
+
+pub fn roots(f: &mut dyn FnMut(f64) -> Result<f64, Error>, a: f64, b: f64, steps: usize, tol: f64) -> Result<Vec<f64>, Error> {
+    let mut roots: Vec<f64> = vec![];
+    let h = (b - a) / steps as f64;
+    let mut x0 = a;
+    let mut y0 = f(x0)?;
+    if y0 == 0.0 { roots.push(x0) }
+    for i in 1..=steps {
+        let x1 = if i == steps { b } else { a + h * i as f64 };
+        let y1 = f(x1)?;
+        if y1 == 0.0 {
+            roots.push(x1);
+        } else if y0 != 0.0 && y0.signum() != y1.signum() && y0.is_finite() && y1.is_finite() {
+            // brent also converges to poles, where the function grows instead of vanishing
+            let root = brent(f, x0, x1, tol)?;
+            if f(root)?.abs() < y0.abs().min(y1.abs()) { roots.push(root) }
+        }
+        x0 = x1; y0 = y1;
+    }
+    Ok(roots)
+}
+
+// an interval with its ends and midpoint evaluated, and its simpson estimate
+#[derive(Clone, Copy)]
+struct Segment { a: f64, fa: f64, m: f64, fm: f64, b: f64, fb: f64, whole: f64 }
+impl Segment {
+    fn new(a: f64, fa: f64, m: f64, fm: f64, b: f64, fb: f64) -> Self {
+        Self { a, fa, m, fm, b, fb, whole: (b - a) / 6.0 * (fa + 4.0 * fm + fb) }
+    }
+}
+
+fn simpson(f: &mut dyn FnMut(f64) -> Result<f64, Error>, s: Segment, tol: f64, depth: usize) -> Result<f64, Error> {
+    let (lm, rm) = ((s.a + s.m) / 2.0, (s.m + s.b) / 2.0);
+    let left = Segment::new(s.a, s.fa, lm, f(lm)?, s.m, s.fm);
+    let right = Segment::new(s.m, s.fm, rm, f(rm)?, s.b, s.fb);
+    let delta = left.whole + right.whole - s.whole;
+    if delta.abs() <= 15.0 * tol { return Ok(left.whole + right.whole + delta / 15.0) }
+    if depth == 0 { return Err(Error::SubdivisionLimit(MAX_DEPTH)) }
+    Ok(simpson(f, left, tol / 2.0, depth - 1)? + simpson(f, right, tol / 2.0, depth - 1)?)
+}
+
+pub fn integrate(f: &mut dyn FnMut(f64) -> Result<f64, Error>, a: f64, b: f64, tol: f64) -> Result<f64, Error> {
+    if tol.is_nan() || tol <= 0.0 { return Err(Error::Tolerance(tol)) }
+    if a == b { return Ok(0.0) }
+    if a > b { return Ok(-integrate(f, b, a, tol)?) }
+    // infinite bounds are mapped onto a finite interval whose ends are never evaluated exactly
+    let mut g: Box<dyn FnMut(f64) -> Result<f64, Error>> = match (a.is_infinite(), b.is_infinite()) {
+        (false, false) => return adaptive(f, a, b, tol),
+        (true, true) => Box::new(|t: f64| {
+            let x = t / (1.0 - t * t);
+            Ok(f(x)? * (1.0 + t * t) / ((1.0 - t * t) * (1.0 - t * t)))
+        }),
+        (false, true) => Box::new(|t: f64| {
+            let x = a + t / (1.0 - t);
+            Ok(f(x)? / ((1.0 - t) * (1.0 - t)))
+        }),
+        (true, false) => Box::new(|t: f64| {
+            let x = b - (1.0 - t) / t;
+            Ok(f(x)? / (t * t))
+        }),
+    };
+    let (l, r) = if a.is_infinite() && b.is_infinite() { (-1.0, 1.0) } else { (0.0, 1.0) };
+    adaptive(&mut g, l + OPEN, r - OPEN, tol)
+}
+
+fn adaptive(f: &mut dyn FnMut(f64) -> Result<f64, Error>, a: f64, b: f64, tol: f64) -> Result<f64, Error> {
+    let m = (a + b) / 2.0;
+    let segment = Segment::new(a, f(a)?, m, f(m)?, b, f(b)?);
+    simpson(f, segment, tol, MAX_DEPTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool { (a - b).abs() < 1e-9 }
+
+    #[test]
+    fn newton_finds_sqrt_two() {
+        let root = newton(&mut |x| Ok(x * x - 2.0), 1.0, TOLERANCE).unwrap();
+        assert!(close(root, 2f64.sqrt()));
+    }
+    #[test]
+    fn newton_reports_no_convergence() {
+        let e = newton(&mut |x| Ok(x * x + 1.0), 0.5, TOLERANCE).unwrap_err();
+        assert_eq!(e, Error::NoConvergence("newton".to_string(), MAX_ITER));
+    }
+    #[test]
+    fn brent_finds_the_fixed_point_of_cos() {
+        let root = brent(&mut |x| Ok(x.cos() - x), 0.0, 1.0, TOLERANCE).unwrap();
+        assert!(close(root, 0.7390851332151607));
+    }
+    #[test]
+    fn bracket_needs_a_sign_change() {
+        assert_eq!(brent(&mut |x| Ok(x * x + 1.0), -1.0, 1.0, TOLERANCE), Err(Error::NoSignChange(-1.0, 1.0)));
+    }
+    #[test]
+    fn bracket_ends_can_be_roots() {
+        assert_eq!(brent(&mut |x| Ok(x - 1.0), 1.0, 3.0, TOLERANCE), Ok(1.0));
+        assert_eq!(brent(&mut |x| Ok(x - 3.0), 1.0, 3.0, TOLERANCE), Ok(3.0));
+    }
+    #[test]
+    fn roots_of_a_cubic() {
+        let found = roots(&mut |x| Ok((x + 2.0) * (x - 0.5) * (x - 3.0)), -5.0, 5.0, 1000, TOLERANCE).unwrap();
+        assert_eq!(found.len(), 3);
+        for (root, expected) in found.iter().zip([-2.0, 0.5, 3.0]) { assert!(close(*root, expected)) }
+    }
+    #[test]
+    fn roots_skip_poles() {
+        assert_eq!(roots(&mut |x| Ok(x.tan()), 1.0, 2.0, 1000, TOLERANCE), Ok(vec![]));
+        assert_eq!(roots(&mut |x| Ok(1.0 / (x - 0.3)), -1.0, 1.0, 1000, TOLERANCE), Ok(vec![]));
+        let found = roots(&mut |x| Ok(x.tan()), 1.0, 4.0, 1000, TOLERANCE).unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(close(found[0], std::f64::consts::PI));
+    }
+    #[test]
+    fn errors_of_the_function_propagate() {
+        let e = newton(&mut |_| Err(Error::NotDefined("y".to_string())), 0.0, TOLERANCE);
+        assert_eq!(e, Err(Error::NotDefined("y".to_string())));
+    }
+    #[test]
+    fn integrate_polynomials_exactly() {
+        assert!(close(integrate(&mut |x| Ok(x * x), 0.0, 3.0, 1e-10).unwrap(), 9.0));
+        assert!(close(integrate(&mut |x| Ok(x * x), 3.0, 0.0, 1e-10).unwrap(), -9.0));
+        assert_eq!(integrate(&mut |x| Ok(x), 2.0, 2.0, 1e-10), Ok(0.0));
+    }
+    #[test]
+    fn integrate_smooth_functions() {
+        assert!(close(integrate(&mut |x| Ok(x.sin()), 0.0, std::f64::consts::PI, 1e-10).unwrap(), 2.0));
+    }
+    #[test]
+    fn integrate_infinite_bounds() {
+        let pi = std::f64::consts::PI;
+        assert!((integrate(&mut |x| Ok((-x * x).exp()), f64::NEG_INFINITY, f64::INFINITY, 1e-10).unwrap() - pi.sqrt()).abs() < 1e-6);
+        assert!((integrate(&mut |x| Ok((-x).exp()), 0.0, f64::INFINITY, 1e-10).unwrap() - 1.0).abs() < 1e-6);
+        assert!((integrate(&mut |x| Ok(1.0 / (1.0 + x * x)), f64::NEG_INFINITY, 0.0, 1e-10).unwrap() - pi / 2.0).abs() < 1e-6);
+    }
+    #[test]
+    fn integrate_reports_the_subdivision_limit() {
+        let e = integrate(&mut |x| Ok(1.0 / x), 0.0, 1.0, 1e-10).unwrap_err();
+        assert_eq!(e, Error::SubdivisionLimit(MAX_DEPTH));
+        assert_eq!(e.to_string(), "ERROR: integration exceeded subdivision depth 50");
+    }
+    #[test]
+    fn integrate_rejects_bad_tolerances() {
+        assert_eq!(integrate(&mut |x| Ok(x), 0.0, 1.0, 0.0), Err(Error::Tolerance(0.0)));
+        assert_eq!(integrate(&mut |x| Ok(x), 0.0, 1.0, -1e-3), Err(Error::Tolerance(-1e-3)));
+        assert!(matches!(integrate(&mut |x| Ok(x), 0.0, 1.0, f64::NAN), Err(Error::Tolerance(_))));
+    }
+}
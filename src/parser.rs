@@ -1,313 +1,506 @@
-use crate::set::*;
-use crate::position::*;
-use crate::error::*;
-use crate::lexer::*;
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum Node {
-    Int{ v: i64, pos: Position }, Float{ v: f64, pos: Position }, Word{ v: String, pos: Position },
-    Vector{ v: Vec<Node>, pos: Position }, Set{ v: Set<Node>, pos: Position },
-    Binary{ op: Token, left: Box<Node>, right: Box<Node>, pos: Position }, Unary{ op: Token, node: Box<Node>, pos: Position },
-    Call{ v: Box<Node>, args: Vec<Node>, pos: Position }, Tuple{ nodes: Vec<Node>, pos: Position },
-    Percent{ node: Box<Node>, pos: Position }, Abs{ node: Box<Node>, pos: Position },
-    Assign { m: bool, id: Box<Node>, expr: Box<Node>, pos: Position },
-    Function { id: Box<Node>, body: Box<Node>, pos: Position },
-}
-impl Node {
-    pub fn pos(&self) -> Position {
-        match self {
-            Self::Int { v: _, pos } => pos.clone(),
-            Self::Float { v: _, pos } => pos.clone(),
-            Self::Word { v: _, pos } => pos.clone(),
-            Self::Vector { v: _, pos } => pos.clone(),
-            Self::Set { v: _, pos } => pos.clone(),
-            Self::Binary { op: _, left: _, right: _, pos } => pos.clone(),
-            Self::Unary { op: _, node: _, pos } => pos.clone(),
-            Self::Call { v: _, args: _, pos } => pos.clone(),
-            Self::Tuple { nodes: _, pos } => pos.clone(),
-            Self::Percent { node: _, pos } => pos.clone(),
-            Self::Abs { node: _, pos } => pos.clone(),
-            Self::Assign { m: _, id: _, expr: _, pos } => pos.clone(),
-            Self::Function { id: _, body: _, pos } => pos.clone(),
-        }
-    }
-}
-impl std::fmt::Display for Node {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Int { v, pos: _ } => write!(f, "{v}"),
-            Self::Float { v, pos: _ } => write!(f, "{v}"),
-            Self::Word { v, pos: _ } => write!(f, "{v}"),
-            Self::Vector { v, pos: _ } => write!(f, "[{}]", v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ")),
-            Self::Set { v, pos: _ } => write!(f, "{{{}}}", v.values.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ")),
-            Self::Binary { op, left, right, pos: _ } => write!(f, "({left} {op} {right})"),
-            Self::Unary { op, node, pos: _ } => write!(f, "({op} {node})"),
-            Self::Call { v, args, pos: _ } => write!(f, "{v}({})", args.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")),
-            Self::Tuple { nodes, pos: _ } => write!(f, "({})", nodes.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")),
-            Self::Percent { node, pos: _ } => write!(f, "{node}%"),
-            Self::Abs { node, pos: _ } => write!(f, "|{node}|"),
-            Self::Assign { m, id, expr, pos } => if *m { write!(f, "{id} := {expr}") } else { write!(f, "{id} :: {expr}") }
-            Self::Function { id, body, pos } => write!(f, "{id} -> {body}"),
-        }
-    }
-}
-
-pub struct Parser {
-    pub tokens: Vec<Token>,
-    pub poses: Vec<Position>,
-    pub idx: usize,
-    pub path: String
-}
-impl Parser {
-    pub fn new(tokens: Vec<Token>, poses: Vec<Position>, path: &str) -> Self {
-        Self { tokens, poses, idx: 0, path: path.to_string() }
-    }
-    pub fn token(&self) -> Token {
-        match self.tokens.get(self.idx) {
-            Some(token) => token.clone(),
-            None => Token::None
-        }
-    }
-    pub fn pos(&self) -> Position {
-        match self.poses.get(self.idx) {
-            Some(pos) => pos.clone(),
-            None => self.poses.last().unwrap().clone()
-        }
-    }
-    pub fn expected(&self, token: Token) -> Result<(), Error> {
-        if self.token() != token { return Err(Error::ExpectedToken(token)) }
-        Ok(())
-    }
-    pub fn expected_unknown(&self, token: Token) -> Result<(), Error> {
-        if self.token() != token { return Err(Error::UnexpectedToken(self.token())) }
-        Ok(())
-    }
-    pub fn advance(&mut self) {
-        self.idx += 1;
-    }
-    pub fn call(&mut self, f: &str) -> Result<Node, Error> {
-        match f {
-            "expr" => self.expr(),
-            "merge" => self.merge(),
-            "arith" => self.arith(),
-            "term" => self.term(),
-            "pow" => self.pow(),
-            "factor" => self.factor(),
-            "percent" => self.percent(),
-            "amount" => self.amount(),
-            "fcall" => self.fcall(),
-            "field" => self.field(),
-            "atom" => self.atom(),
-            _ => Err(Error::Todo(f.to_string()))
-        }
-    }
-    pub fn binary(&mut self, ops: Vec<Token>, f: &str) -> Result<Node, Error> {
-        let mut left = self.call(f)?;
-        let start = left.pos();
-        while ops.contains(&self.token()) {
-            let op = self.token();
-            self.advance();
-            let right = self.call(f)?;
-            left = Node::Binary {
-                op,
-                left: Box::new(left.clone()),
-                right: Box::new(right.clone()),
-                pos: Position(start.0.clone(), start.1.start..right.pos().1.end)
-            }
-        }
-        Ok(left)
-    }
-    pub fn parse(&mut self) -> Result<Node, Error> {
-        let node = self.expr()?;
-        self.expected_unknown(Token::None)?;
-        Ok(node)
-    }
-    pub fn expr(&mut self) -> Result<Node, Error> {
-        let id = self.func()?;
-        if self.token() == Token::Assign || self.token() == Token::Def {
-            let tok = self.token();
-            if let Node::Word { v, pos } = &id {
-                self.advance();
-                let expr = self.func()?;
-                return Ok(Node::Assign{
-                    m: tok == Token::Assign,
-                    id: Box::new(id.clone()),
-                    expr: Box::new(expr.clone()),
-                    pos: Position(self.path.clone(), pos.1.start..expr.pos().1.end)
-                })
-            } else {
-                return Err(Error::UnexpectedToken(self.token()))
-            }
-        }
-        Ok(id)
-    }
-    pub fn func(&mut self) -> Result<Node, Error> {
-        let id = self.comp()?;
-        if self.token() == Token::ArrowRight {
-            self.advance();
-            let node = self.expr()?;
-            return Ok(Node::Function {
-                id: Box::new(id.clone()),
-                body: Box::new(node.clone()),
-                pos: Position(self.path.clone(), id.pos().1.start..node.pos().1.end)
-            })
-        }
-        Ok(id)
-    }
-    pub fn comp(&mut self) -> Result<Node, Error> {
-        self.binary(vec![Token::Equal, Token::NotEqual, Token::Less, Token::Greater, Token::LessEqual, Token::GreaterEqual],
-            "merge")
-    }
-    pub fn merge(&mut self) -> Result<Node, Error> {
-        self.binary(vec![Token::Concat, Token::Remove], "arith")
-    }
-    pub fn arith(&mut self) -> Result<Node, Error> {
-        self.binary(vec![Token::Plus, Token::Minus, Token::PlusMinus], "term")
-    }
-    pub fn term(&mut self) -> Result<Node, Error> {
-        self.binary(vec![Token::Star, Token::Slash], "pow")
-    }
-    pub fn pow(&mut self) -> Result<Node, Error> {
-        self.binary(vec![Token::Exponent], "factor")
-    }
-    pub fn factor(&mut self) -> Result<Node, Error> {
-        if self.token() == Token::Minus {
-            let start = self.pos();
-            self.advance();
-            let node = self.factor()?;
-            return Ok(Node::Unary {
-                op: Token::Minus,
-                node: Box::new(node.clone()),
-                pos: Position(self.path.clone(),
-                start.1.start..node.pos().1.end)
-            })
-        }
-        self.percent()
-    }
-    pub fn percent(&mut self) -> Result<Node, Error> {
-        let mut node = self.amount()?;
-        while self.token() == Token::Percent {
-            let stop = self.pos();
-            self.advance();
-            node = Node::Percent { node: Box::new(node.clone()), pos: Position(self.path.clone(), node.pos().1.start..stop.1.end) }
-        }
-        Ok(node)
-    }
-    pub fn amount(&mut self) -> Result<Node, Error> {
-        if self.token() == Token::Amount {
-            let start = self.pos();
-            self.advance();
-            let node = self.fcall()?;
-            return Ok(Node::Unary {
-                op: Token::Amount,
-                node: Box::new(node.clone()),
-                pos: Position(self.path.clone(),
-                start.1.start..node.pos().1.end)
-            })
-        }
-        self.fcall()
-    }
-    pub fn fcall(&mut self) -> Result<Node, Error> {
-        let node = self.field()?;
-        if self.token() == Token::EvalIn {
-            self.advance();
-            let mut nodes: Vec<Node> = vec![];
-            let pos = self.pos().1;
-            let (start, mut stop) = (pos.start, pos.end);
-            while self.token() != Token::EvalOut && self.token() != Token::None {
-                let node_ = self.expr()?;
-                stop = node_.pos().1.end;
-                nodes.push(node_);
-                if self.token() != Token::EvalOut {
-                    self.expected(Token::Sep)?;
-                    self.advance();
-                }
-            }
-            self.expected(Token::EvalOut)?;
-            self.advance();
-            return Ok(Node::Call { v: Box::new(node), args: nodes, pos: Position(self.path.clone(), start..stop) })
-        }
-        Ok(node)
-    }
-    pub fn field(&mut self) -> Result<Node, Error> {
-        self.binary(vec![Token::Field], "atom")
-    }
-    pub fn atom(&mut self) -> Result<Node, Error> {
-        match self.token() {
-            Token::Int(v) => {
-                self.advance();
-                Ok(Node::Int { v, pos: self.pos() })
-            }
-            Token::Float(v) => {
-                self.advance();
-                Ok(Node::Float { v, pos: self.pos() })
-            }
-            Token::Word(v) => {
-                self.advance();
-                Ok(Node::Word { v, pos: self.pos() })
-            }
-            Token::EvalIn => {
-                let start = self.pos();
-                self.advance();
-                let node = self.expr()?;
-                if self.token() == Token::Sep {
-                    let mut nodes: Vec<Node> = vec![node];
-                    while self.token() == Token::Sep {
-                        self.advance();
-                        let node = self.expr()?;
-                        nodes.push(node);
-                    }
-                    self.expected(Token::EvalOut)?;
-                    let stop = self.pos();
-                    self.advance();
-                    return Ok(Node::Tuple { nodes, pos: Position(self.path.clone(), start.1.start..stop.1.end) })
-                }
-                self.expected(Token::EvalOut)?;
-                self.advance();
-                Ok(node)
-            }
-            Token::VectorIn => {
-                self.advance();
-                let mut nodes: Vec<Node> = vec![];
-                let pos = self.pos().1;
-                let (start, mut stop) = (pos.start, pos.end);
-                while self.token() != Token::VectorOut && self.token() != Token::None {
-                    let node = self.expr()?;
-                    stop = node.pos().1.end;
-                    nodes.push(node);
-                }
-                self.expected(Token::VectorOut)?;
-                self.advance();
-                Ok(Node::Vector { v: nodes, pos: Position(self.path.clone(), start..stop) })
-            }
-            Token::SetIn => {
-                self.advance();
-                let mut nodes: Set<Node> = Set::new();
-                let pos = self.pos().1;
-                let (start, mut stop) = (pos.start, pos.end);
-                while self.token() != Token::SetOut && self.token() != Token::None {
-                    let node = self.expr()?;
-                    stop = node.pos().1.end;
-                    nodes.add(node);
-                }
-                self.expected(Token::SetOut)?;
-                self.advance();
-                Ok(Node::Set { v: nodes, pos: Position(self.path.clone(), start..stop) })
-            }
-            Token::Abs => {
-                let start = self.pos();
-                self.advance();
-                let node = self.expr()?;
-                self.expected(Token::Abs)?;
-                let stop = self.pos();
-                self.advance();
-                Ok(Node::Abs{ node: Box::new(node), pos: Position(self.path.clone(), start.1.start..stop.1.end) })
-            }
-            _ => Err(Error::UnexpectedToken(self.token()))
-        }
-    }
-}
-
-pub fn parse(tokens: Vec<Token>, poses: Vec<Position>, path: &str) -> Result<Node, Error> {
-    Parser::new(tokens, poses, path).parse()
-}
\ No newline at end of file
+use crate::set::*;
+use crate::position::*;
+use crate::error::*;
+use crate::lexer::*;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Int{ v: i64, pos: Position }, Float{ v: f64, pos: Position }, Word{ v: String, pos: Position },
+    Vector{ v: Vec<Node>, pos: Position }, Set{ v: Set<Node>, pos: Position },
+    Binary{ op: Token, left: Box<Node>, right: Box<Node>, pos: Position }, Unary{ op: Token, node: Box<Node>, pos: Position },
+    Call{ v: Box<Node>, args: Vec<Node>, partial: bool, pos: Position }, Tuple{ nodes: Vec<Node>, pos: Position },
+    Percent{ node: Box<Node>, pos: Position }, Abs{ node: Box<Node>, pos: Position },
+    Assign { m: bool, id: Box<Node>, expr: Box<Node>, pos: Position },
+    Function { id: Box<Node>, body: Rc<Node>, pos: Position },
+    Condition { cond: Box<Node>, case: Box<Node>, default: Box<Node>, pos: Position },
+    /// an argument passed by name, `id: expr`, only found in the arguments of a call
+    Named { id: String, expr: Box<Node>, pos: Position },
+    Import { path: String, pos: Position }, Block { nodes: Vec<Node>, pos: Position },
+    Error { pos: Position },
+}
+impl Node {
+    pub fn pos(&self) -> Position {
+        match self {
+            Self::Int { v: _, pos } => pos.clone(),
+            Self::Float { v: _, pos } => pos.clone(),
+            Self::Word { v: _, pos } => pos.clone(),
+            Self::Vector { v: _, pos } => pos.clone(),
+            Self::Set { v: _, pos } => pos.clone(),
+            Self::Binary { op: _, left: _, right: _, pos } => pos.clone(),
+            Self::Unary { op: _, node: _, pos } => pos.clone(),
+            Self::Call { v: _, args: _, partial: _, pos } => pos.clone(),
+            Self::Tuple { nodes: _, pos } => pos.clone(),
+            Self::Percent { node: _, pos } => pos.clone(),
+            Self::Abs { node: _, pos } => pos.clone(),
+            Self::Assign { m: _, id: _, expr: _, pos } => pos.clone(),
+            Self::Function { id: _, body: _, pos } => pos.clone(),
+            Self::Condition { cond: _, case: _, default: _, pos } => pos.clone(),
+            Self::Named { id: _, expr: _, pos } => pos.clone(),
+            Self::Import { path: _, pos } => pos.clone(),
+            Self::Block { nodes: _, pos } => pos.clone(),
+            Self::Error { pos } => pos.clone(),
+        }
+    }
+}
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int { v, pos: _ } => write!(f, "{v}"),
+            Self::Float { v, pos: _ } => write!(f, "{v}"),
+            Self::Word { v, pos: _ } => write!(f, "{v}"),
+            Self::Vector { v, pos: _ } => write!(f, "[{}]", v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ")),
+            Self::Set { v, pos: _ } => write!(f, "{{{}}}", v.values.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ")),
+            Self::Binary { op, left, right, pos: _ } => write!(f, "({left} {op} {right})"),
+            Self::Unary { op, node, pos: _ } => write!(f, "({op} {node})"),
+            Self::Call { v, args, partial, pos: _ } => {
+                let mut args = args.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+                if *partial { args.push("..".to_string()) }
+                write!(f, "{v}({})", args.join(", "))
+            }
+            Self::Tuple { nodes, pos: _ } => write!(f, "({})", nodes.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")),
+            Self::Percent { node, pos: _ } => write!(f, "{node}%"),
+            Self::Abs { node, pos: _ } => write!(f, "|{node}|"),
+            Self::Assign { m, id, expr, pos: _ } => if *m { write!(f, "{id} := {expr}") } else { write!(f, "{id} :: {expr}") }
+            Self::Function { id, body, pos: _ } => write!(f, "{id} -> {body}"),
+            Self::Condition { cond, case, default, pos: _ } => write!(f, "({cond} ? {case} : {default})"),
+            Self::Named { id, expr, pos: _ } => write!(f, "{id}: {expr}"),
+            Self::Import { path, pos: _ } => write!(f, "import \"{path}\""),
+            Self::Block { nodes, pos: _ } => write!(f, "{}", nodes.iter().map(|x| x.to_string()).collect::<Vec<String>>().join("; ")),
+            Self::Error { pos: _ } => write!(f, "<error>"),
+        }
+    }
+}
+
+pub const MAX_NESTING: usize = 1000;
+
+pub struct Parser {
+    pub tokens: Vec<Token>,
+    pub poses: Vec<Position>,
+    pub idx: usize,
+    pub path: String,
+    pub depth: usize,
+    pub errors: Vec<Error>,
+}
+impl Parser {
+    pub fn new(tokens: Vec<Token>, poses: Vec<Position>, path: &str) -> Self {
+        Self { tokens, poses, idx: 0, path: path.to_string(), depth: 0, errors: vec![] }
+    }
+    pub fn nested(&mut self, f: fn(&mut Self) -> Result<Node, Error>) -> Result<Node, Error> {
+        if self.depth >= MAX_NESTING { return Err(Error::RecursionLimit(MAX_NESTING)) }
+        self.depth += 1;
+        let node = crate::interpreter::grow(|| f(self));
+        self.depth -= 1;
+        node
+    }
+    pub fn token(&self) -> Token {
+        match self.tokens.get(self.idx) {
+            Some(token) => token.clone(),
+            None => Token::None
+        }
+    }
+    pub fn pos(&self) -> Position {
+        match self.poses.get(self.idx) {
+            Some(pos) => pos.clone(),
+            None => self.poses.last().unwrap().clone()
+        }
+    }
+    pub fn error_pos(&self) -> Position {
+        match self.poses.get(self.idx) {
+            Some(pos) => pos.clone(),
+            None => {
+                let end = self.poses.last().unwrap().1.end;
+                Position(self.path.clone(), end..end + 1)
+            }
+        }
+    }
+    pub fn expected(&self, token: Token) -> Result<(), Error> {
+        if self.token() != token { return Err(Error::ExpectedToken(token)) }
+        Ok(())
+    }
+    pub fn expected_unknown(&self, token: Token) -> Result<(), Error> {
+        if self.token() != token { return Err(Error::UnexpectedToken(self.token())) }
+        Ok(())
+    }
+    pub fn advance(&mut self) {
+        self.idx += 1;
+    }
+    pub fn call(&mut self, f: &str) -> Result<Node, Error> {
+        match f {
+            "expr" => self.expr(),
+            "comp" => self.comp(),
+            "merge" => self.merge(),
+            "arith" => self.arith(),
+            "term" => self.term(),
+            "pow" => self.pow(),
+            "factor" => self.factor(),
+            "percent" => self.percent(),
+            "amount" => self.amount(),
+            "fcall" => self.fcall(),
+            "field" => self.field(),
+            "atom" => self.atom(),
+            _ => Err(Error::Todo(f.to_string()))
+        }
+    }
+    pub fn binary(&mut self, ops: Vec<Token>, f: &str) -> Result<Node, Error> {
+        let mut left = self.call(f)?;
+        let start = left.pos();
+        while ops.contains(&self.token()) {
+            let op = self.token();
+            self.advance();
+            let right = self.call(f)?;
+            let pos = Position(start.0.clone(), start.1.start..right.pos().1.end);
+            left = Node::Binary { op, left: Box::new(left), right: Box::new(right), pos }
+        }
+        Ok(left)
+    }
+    pub fn recover(&mut self, e: Error, stops: &[Token]) -> Node {
+        let start = self.error_pos();
+        self.errors.push(e.at(start.clone()));
+        let idx = self.idx;
+        let mut depth: i32 = 0;
+        let mut stop = start.1.start;
+        loop {
+            let token = self.token();
+            if token == Token::None || (depth == 0 && stops.contains(&token)) { break }
+            match token {
+                Token::EvalIn | Token::VectorIn | Token::SetIn => depth += 1,
+                Token::EvalOut | Token::VectorOut | Token::SetOut => {
+                    // a closing bracket that is not expected here is skipped if nothing else was, otherwise it is left to the enclosing list
+                    if depth == 0 && self.idx > idx { break }
+                    depth = (depth - 1).max(0);
+                }
+                _ => {}
+            }
+            stop = self.pos().1.end;
+            self.advance();
+        }
+        Node::Error { pos: Position(self.path.clone(), start.1.start..stop.max(start.1.end)) }
+    }
+    pub fn element(&mut self, stops: &[Token]) -> Node {
+        match self.expr() {
+            Ok(node) => node,
+            Err(e) => self.recover(e, stops)
+        }
+    }
+    // whatever follows an argument up to the next ',' or ')' is reported once and skipped
+    pub fn separated(&mut self, expected: Token) {
+        if self.token() != Token::EvalOut && self.token() != Token::Sep && self.token() != Token::None {
+            self.recover(Error::ExpectedToken(expected), &[Token::Sep, Token::EvalOut]);
+        }
+    }
+    pub fn program(&mut self) -> Node {
+        let start = self.pos();
+        let mut nodes: Vec<Node> = vec![];
+        while self.token() != Token::None {
+            if self.token() == Token::End {
+                self.advance();
+                continue
+            }
+            let node = self.element(&[Token::End]);
+            nodes.push(node);
+            if self.token() != Token::End && self.token() != Token::None {
+                self.recover(Error::ExpectedToken(Token::End), &[Token::End]);
+            }
+        }
+        if nodes.len() == 1 { return nodes.pop().unwrap() }
+        let stop = self.poses.last().unwrap().1.end;
+        Node::Block { nodes, pos: Position(self.path.clone(), start.1.start..stop) }
+    }
+    pub fn parse(&mut self) -> Result<Node, Error> {
+        let node = self.program();
+        match self.errors.len() {
+            0 => Ok(node),
+            1 => Err(self.errors.pop().unwrap()),
+            _ => Err(Error::Many(self.errors.clone()))
+        }
+    }
+    pub fn expr(&mut self) -> Result<Node, Error> {
+        self.nested(Self::assign)
+    }
+    pub fn assign(&mut self) -> Result<Node, Error> {
+        let id = self.cond()?;
+        if self.token() == Token::Assign || self.token() == Token::Def {
+            let tok = self.token();
+            if let Node::Word { pos, .. } = &id {
+                self.advance();
+                let expr = self.cond()?;
+                let pos = Position(self.path.clone(), pos.1.start..expr.pos().1.end);
+                return Ok(Node::Assign{ m: tok == Token::Assign, id: Box::new(id), expr: Box::new(expr), pos })
+            } else {
+                return Err(Error::UnexpectedToken(self.token()))
+            }
+        }
+        Ok(id)
+    }
+    pub fn cond(&mut self) -> Result<Node, Error> {
+        let cond = self.func()?;
+        if self.token() == Token::Option {
+            self.advance();
+            let case = self.expr()?;
+            self.expected(Token::Rep)?;
+            self.advance();
+            let default = self.expr()?;
+            let pos = Position(self.path.clone(), cond.pos().1.start..default.pos().1.end);
+            return Ok(Node::Condition { cond: Box::new(cond), case: Box::new(case), default: Box::new(default), pos })
+        }
+        Ok(cond)
+    }
+    pub fn func(&mut self) -> Result<Node, Error> {
+        let id = self.compose()?;
+        if self.token() == Token::ArrowRight {
+            self.advance();
+            let node = self.expr()?;
+            let pos = Position(self.path.clone(), id.pos().1.start..node.pos().1.end);
+            return Ok(Node::Function { id: Box::new(id), body: Rc::new(node), pos })
+        }
+        Ok(id)
+    }
+    pub fn compose(&mut self) -> Result<Node, Error> {
+        self.binary(vec![Token::Compose], "comp")
+    }
+    pub fn comp(&mut self) -> Result<Node, Error> {
+        self.binary(vec![Token::Equal, Token::NotEqual, Token::Less, Token::Greater, Token::LessEqual, Token::GreaterEqual],
+            "merge")
+    }
+    pub fn merge(&mut self) -> Result<Node, Error> {
+        self.binary(vec![Token::Concat, Token::Remove], "arith")
+    }
+    pub fn arith(&mut self) -> Result<Node, Error> {
+        self.binary(vec![Token::Plus, Token::Minus, Token::PlusMinus], "term")
+    }
+    pub fn term(&mut self) -> Result<Node, Error> {
+        self.binary(vec![Token::Star, Token::Slash], "pow")
+    }
+    pub fn pow(&mut self) -> Result<Node, Error> {
+        self.binary(vec![Token::Exponent], "factor")
+    }
+    pub fn factor(&mut self) -> Result<Node, Error> {
+        if self.token() == Token::Minus {
+            let start = self.pos();
+            self.advance();
+            let node = self.nested(Self::factor)?;
+            let pos = Position(self.path.clone(), start.1.start..node.pos().1.end);
+            return Ok(Node::Unary { op: Token::Minus, node: Box::new(node), pos })
+        }
+        self.percent()
+    }
+    pub fn percent(&mut self) -> Result<Node, Error> {
+        let mut node = self.amount()?;
+        while self.token() == Token::Percent {
+            let stop = self.pos();
+            self.advance();
+            let pos = Position(self.path.clone(), node.pos().1.start..stop.1.end);
+            node = Node::Percent { node: Box::new(node), pos }
+        }
+        Ok(node)
+    }
+    pub fn amount(&mut self) -> Result<Node, Error> {
+        if self.token() == Token::Amount {
+            let start = self.pos();
+            self.advance();
+            let node = self.fcall()?;
+            let pos = Position(self.path.clone(), start.1.start..node.pos().1.end);
+            return Ok(Node::Unary { op: Token::Amount, node: Box::new(node), pos })
+        }
+        self.fcall()
+    }
+    pub fn fcall(&mut self) -> Result<Node, Error> {
+        let mut node = self.field()?;
+        while self.token() == Token::EvalIn {
+            self.advance();
+            let mut nodes: Vec<Node> = vec![];
+            let mut partial = false;
+            let start = node.pos().1.start;
+            while self.token() != Token::EvalOut && self.token() != Token::None {
+                if self.token() == Token::Range && self.tokens.get(self.idx + 1) == Some(&Token::EvalOut) {
+                    partial = true;
+                    self.advance();
+                    break
+                }
+                let node_ = self.argument();
+                nodes.push(node_);
+                self.separated(Token::Sep);
+                if self.token() == Token::Sep { self.advance() }
+            }
+            self.expected(Token::EvalOut)?;
+            let stop = self.pos().1.end;
+            self.advance();
+            node = Node::Call { v: Box::new(node), args: nodes, partial, pos: Position(self.path.clone(), start..stop) };
+        }
+        Ok(node)
+    }
+    // `id: expr` passes an argument by name, anything else is passed by position
+    pub fn argument(&mut self) -> Node {
+        let (Token::Word(id), Some(Token::Rep)) = (self.token(), self.tokens.get(self.idx + 1)) else {
+            return self.element(&[Token::Sep, Token::EvalOut])
+        };
+        let start = self.pos();
+        self.advance();
+        self.advance();
+        let expr = self.element(&[Token::Sep, Token::EvalOut]);
+        let pos = Position(self.path.clone(), start.1.start..expr.pos().1.end);
+        Node::Named { id, expr: Box::new(expr), pos }
+    }
+    pub fn field(&mut self) -> Result<Node, Error> {
+        self.binary(vec![Token::Field], "atom")
+    }
+    pub fn atom(&mut self) -> Result<Node, Error> {
+        match self.token() {
+            Token::Int(v) => {
+                let pos = self.pos();
+                self.advance();
+                Ok(Node::Int { v, pos })
+            }
+            Token::Float(v) => {
+                let pos = self.pos();
+                self.advance();
+                Ok(Node::Float { v, pos })
+            }
+            Token::Word(v) => {
+                let pos = self.pos();
+                self.advance();
+                Ok(Node::Word { v, pos })
+            }
+            Token::Import => {
+                let start = self.pos();
+                self.advance();
+                if let Token::Str(path) = self.token() {
+                    let stop = self.pos();
+                    self.advance();
+                    return Ok(Node::Import { path, pos: Position(self.path.clone(), start.1.start..stop.1.end) })
+                }
+                Err(Error::UnexpectedToken(self.token()))
+            }
+            Token::Range => {
+                let start = self.pos();
+                self.advance();
+                let node = self.nested(Self::atom)?;
+                let pos = Position(self.path.clone(), start.1.start..node.pos().1.end);
+                Ok(Node::Unary { op: Token::Range, node: Box::new(node), pos })
+            }
+            Token::EvalIn => {
+                let start = self.pos();
+                self.advance();
+                let node = self.element(&[Token::Sep, Token::EvalOut]);
+                self.separated(Token::EvalOut);
+                if self.token() == Token::Sep {
+                    let mut nodes: Vec<Node> = vec![node];
+                    while self.token() == Token::Sep {
+                        self.advance();
+                        let node = self.element(&[Token::Sep, Token::EvalOut]);
+                        nodes.push(node);
+                        self.separated(Token::EvalOut);
+                    }
+                    self.expected(Token::EvalOut)?;
+                    let stop = self.pos();
+                    self.advance();
+                    return Ok(Node::Tuple { nodes, pos: Position(self.path.clone(), start.1.start..stop.1.end) })
+                }
+                self.expected(Token::EvalOut)?;
+                self.advance();
+                Ok(node)
+            }
+            Token::VectorIn => {
+                let start = self.pos().1.start;
+                self.advance();
+                let mut nodes: Vec<Node> = vec![];
+                while self.token() != Token::VectorOut && self.token() != Token::None {
+                    let node = self.element(&[Token::VectorOut]);
+                    nodes.push(node);
+                }
+                self.expected(Token::VectorOut)?;
+                let stop = self.pos().1.end;
+                self.advance();
+                Ok(Node::Vector { v: nodes, pos: Position(self.path.clone(), start..stop) })
+            }
+            Token::SetIn => {
+                let start = self.pos().1.start;
+                self.advance();
+                let mut nodes: Set<Node> = Set::new();
+                while self.token() != Token::SetOut && self.token() != Token::None {
+                    let node = self.element(&[Token::SetOut]);
+                    nodes.add(node);
+                }
+                self.expected(Token::SetOut)?;
+                let stop = self.pos().1.end;
+                self.advance();
+                Ok(Node::Set { v: nodes, pos: Position(self.path.clone(), start..stop) })
+            }
+            Token::Abs => {
+                let start = self.pos();
+                self.advance();
+                let node = self.expr()?;
+                self.expected(Token::Abs)?;
+                let stop = self.pos();
+                self.advance();
+                Ok(Node::Abs{ node: Box::new(node), pos: Position(self.path.clone(), start.1.start..stop.1.end) })
+            }
+            _ => Err(Error::UnexpectedToken(self.token()))
+        }
+    }
+}
+
+pub fn parse(tokens: Vec<Token>, poses: Vec<Position>, path: &str) -> Result<Node, Error> {
+    Parser::new(tokens, poses, path).parse()
+}
+pub fn parse_recover(tokens: Vec<Token>, poses: Vec<Position>, path: &str) -> (Node, Vec<Error>) {
+    let mut parser = Parser::new(tokens, poses, path);
+    let node = parser.program();
+    (node, parser.errors)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(text: &str) -> String {
+        let (tokens, poses) = lex(text, "test").unwrap();
+        parse(tokens, poses, "test").unwrap().to_string()
+    }
+
+    #[test]
+    fn conditions_bind_looser_than_comparisons() {
+        assert_eq!(tree("x < 1 ? 2 : 3 + 4"), "((x < 1) ? 2 : (3 + 4))");
+        assert_eq!(tree("a ? b : c ? d : e"), "(a ? b : (c ? d : e))");
+        assert_eq!(tree("f := n -> n = 0 ? 1 : 2"), "f := n -> ((n = 0) ? 1 : 2)");
+    }
+    #[test]
+    fn conditions_need_both_branches() {
+        let (tokens, poses) = lex("a ? b", "test").unwrap();
+        assert_eq!(parse(tokens, poses, "test").unwrap_err().to_string(), "ERROR: expected ':'");
+    }
+    fn errors(text: &str) -> Vec<String> {
+        let (tokens, poses) = lex(text, "test").unwrap();
+        let (_, errors) = parse_recover(tokens, poses, "test");
+        errors.iter().map(|e| format!("{e} at {:?}", e.pos().unwrap().1)).collect()
+    }
+
+    #[test]
+    fn statements_need_a_separator() {
+        assert_eq!(errors("1 2 3"), vec!["ERROR: expected ';' at 2..3"]);
+        assert_eq!(errors("a := 1\nb := 2"), vec!["ERROR: expected ';' at 7..8"]);
+        assert_eq!(errors("a := 1 b := 2; c := 3 d"), vec!["ERROR: expected ';' at 7..8", "ERROR: expected ';' at 22..23"]);
+    }
+    #[test]
+    fn each_error_is_reported_once() {
+        assert_eq!(errors("(1 +) + (2 +)"), vec!["ERROR: unexpected ')' at 4..5", "ERROR: unexpected ')' at 12..13"]);
+        assert_eq!(errors("f(1 2, 3)"), vec!["ERROR: expected ',' at 4..5"]);
+        assert_eq!(errors("f(1 2, 3 4)"), vec!["ERROR: expected ',' at 4..5", "ERROR: expected ',' at 9..10"]);
+        assert_eq!(errors("sqrt(1 +, 2 *)"), vec!["ERROR: unexpected ',' at 8..9", "ERROR: unexpected ')' at 13..14"]);
+        assert_eq!(errors("(1, 2 3)"), vec!["ERROR: expected ')' at 6..7"]);
+        assert_eq!(errors("(1 2) + (3 4)"), vec!["ERROR: expected ')' at 3..4", "ERROR: expected ')' at 11..12"]);
+        assert_eq!(errors("(1 +, 2 *) + 3"), vec!["ERROR: unexpected ',' at 4..5", "ERROR: unexpected ')' at 9..10"]);
+        assert_eq!(errors("[1 + ] + [2 *]"), vec!["ERROR: unexpected ']' at 5..6", "ERROR: unexpected ']' at 13..14"]);
+        assert_eq!(errors("f(1 +) + g(2 *); h(,)"), vec!["ERROR: unexpected ')' at 5..6", "ERROR: unexpected ')' at 14..15", "ERROR: unexpected ',' at 19..20"]);
+    }
+    // each operator used to copy the whole chain parsed so far, a thousand terms took a third of a second
+    #[test]
+    fn long_chains_parse_in_linear_time() {
+        let text = format!("{}1", "1 + ".repeat(20000));
+        let (tokens, poses) = lex(&text, "test").unwrap();
+        let start = std::time::Instant::now();
+        let node = parse(tokens, poses, "test").unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(node.pos().1, 0..text.len());
+    }
+}
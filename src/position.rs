@@ -1,3 +1,3 @@
-use std::ops::Range;
-#[derive(Debug, Clone, PartialEq)]
+use std::ops::Range;
+#[derive(Debug, Clone, PartialEq)]
 pub struct Position(pub String, pub Range<usize>);
\ No newline at end of file
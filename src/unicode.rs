@@ -0,0 +1,238 @@
+use crate::value::*;
+use crate::lexer::*;
+use crate::parser::*;
+use crate::format::precedence;
+use crate::latex::{GREEK, CAPITALS, lambda};
+
+const LETTERS: &str = "αβγδεζηθικλμνξοπρστυφχψω";
+const CAPITAL_LETTERS: &str = "ΓΔΘΛΞΠΣΥΦΨΩ";
+const SUPERSCRIPTS: [(char, char); 39] = [('0', '⁰'), ('1', '¹'), ('2', '²'), ('3', '³'), ('4', '⁴'), ('5', '⁵'), ('6', '⁶'), ('7', '⁷'),
+    ('8', '⁸'), ('9', '⁹'), ('+', '⁺'), ('-', '⁻'), ('−', '⁻'), ('=', '⁼'), ('(', '⁽'), (')', '⁾'), ('a', 'ᵃ'), ('b', 'ᵇ'), ('c', 'ᶜ'),
+    ('d', 'ᵈ'), ('e', 'ᵉ'), ('f', 'ᶠ'), ('g', 'ᵍ'), ('h', 'ʰ'), ('i', 'ⁱ'), ('j', 'ʲ'), ('k', 'ᵏ'), ('l', 'ˡ'), ('m', 'ᵐ'), ('n', 'ⁿ'),
+    ('o', 'ᵒ'), ('p', 'ᵖ'), ('r', 'ʳ'), ('s', 'ˢ'), ('t', 'ᵗ'), ('u', 'ᵘ'), ('v', 'ᵛ'), ('w', 'ʷ'), ('x', 'ˣ')];
+const SUBSCRIPTS: [(char, char); 27] = [('0', '₀'), ('1', '₁'), ('2', '₂'), ('3', '₃'), ('4', '₄'), ('5', '₅'), ('6', '₆'), ('7', '₇'),
+    ('8', '₈'), ('9', '₉'), ('+', '₊'), ('-', '₋'), ('−', '₋'), ('=', '₌'), ('(', '₍'), (')', '₎'), ('a', 'ₐ'), ('e', 'ₑ'), ('h', 'ₕ'),
+    ('i', 'ᵢ'), ('j', 'ⱼ'), ('k', 'ₖ'), ('m', 'ₘ'), ('n', 'ₙ'), ('o', 'ₒ'), ('t', 'ₜ'), ('x', 'ₓ')];
+
+// maps every character of s with table, or gives up if one of them has no counterpart
+fn script(s: &str, table: &[(char, char)]) -> Option<String> {
+    s.chars().filter(|c| *c != ' ').map(|c| table.iter().find(|(from, _)| *from == c).map(|(_, to)| *to)).collect()
+}
+pub fn word(v: &str) -> String {
+    match v {
+        "PI" => return "π".to_string(),
+        "TAU" => return "τ".to_string(),
+        "E" => return "e".to_string(),
+        "inf" => return "∞".to_string(),
+        _ => {}
+    }
+    if let Some(idx) = GREEK.iter().position(|x| *x == v) { return LETTERS.chars().nth(idx).unwrap().to_string() }
+    if let Some(idx) = CAPITALS.iter().position(|x| *x == v) { return CAPITAL_LETTERS.chars().nth(idx).unwrap().to_string() }
+    if let Some((name, index)) = v.split_once('_') {
+        if let (false, Some(index)) = (name.is_empty(), script(index, &SUBSCRIPTS)) { return format!("{}{index}", word(name)) }
+    }
+    v.to_string()
+}
+fn float(v: f64) -> String {
+    if v.is_nan() { return "NaN".to_string() }
+    if v.is_infinite() { return if v > 0.0 { "∞".to_string() } else { "−∞".to_string() } }
+    v.to_string().replace('-', "−")
+}
+
+fn operand(node: &Node, min: usize) -> String {
+    if precedence(node) < min { return format!("({})", unicode(node)) }
+    unicode(node)
+}
+fn list(nodes: &[Node], sep: &str) -> String {
+    nodes.iter().map(unicode).collect::<Vec<String>>().join(sep)
+}
+fn power(base: String, exponent: String) -> String {
+    match script(&exponent, &SUPERSCRIPTS) {
+        Some(exponent) => format!("{base}{exponent}"),
+        None => format!("{base}^({exponent})"),
+    }
+}
+fn call(v: &Node, args: &[Node], partial: bool) -> String {
+    if let (Node::Word { v: name, .. }, false) = (v, partial) {
+        match (name.as_str(), args) {
+            ("sqrt", [x]) => return format!("√{}", operand(x, 12)),
+            ("abs", [x]) => return format!("|{}|", unicode(x)),
+            ("floor", [x]) => return format!("⌊{}⌋", unicode(x)),
+            ("ceil", [x]) => return format!("⌈{}⌉", unicode(x)),
+            ("filter", [p, s]) => if let Some((x, body)) = lambda(p) {
+                return format!("{{{} ∈ {} | {}}}", word(x), unicode(s), unicode(body))
+            }
+            ("map", [f, s]) => if let Some((x, body)) = lambda(f) {
+                return format!("{{{} | {} ∈ {}}}", unicode(body), word(x), unicode(s))
+            }
+            ("integrate", [f, a, b]) => if let Some((x, body)) = lambda(f) {
+                let bounds = match (script(&unicode(a), &SUBSCRIPTS), script(&unicode(b), &SUPERSCRIPTS)) {
+                    (Some(a), Some(b)) => format!("{a}{b}"),
+                    _ => format!("[{}, {}]", unicode(a), unicode(b)),
+                };
+                return format!("∫{bounds} {} d{}", unicode(body), word(x))
+            }
+            _ => {}
+        }
+    }
+    let mut args = args.iter().map(unicode).collect::<Vec<String>>();
+    if partial { args.push("…".to_string()) }
+    format!("{}({})", operand(v, 12), args.join(", "))
+}
+
+/// Renders an expression as it was written, with math symbols in place of the ascii ones.
+pub fn unicode(node: &Node) -> String {
+    match node {
+        Node::Int { v, .. } => v.to_string(),
+        Node::Float { v, .. } => float(*v),
+        Node::Word { v, .. } => word(v),
+        Node::Vector { v, .. } => format!("[{}]", list(v, " ")),
+        Node::Set { v, .. } if v.values.is_empty() => "∅".to_string(),
+        Node::Set { v, .. } => format!("{{{}}}", list(&v.values, ", ")),
+        Node::Tuple { nodes, .. } => format!("({})", list(nodes, ", ")),
+        Node::Binary { op: Token::Exponent, left, right, .. } => power(operand(left, 12), unicode(right)),
+        Node::Binary { op: Token::Field, left, right, .. } => format!("{}.{}", operand(left, 13), operand(right, 14)),
+        Node::Binary { op, left, right, .. } => {
+            let symbol = match op {
+                Token::Minus => "−".to_string(),
+                Token::Star => "·".to_string(),
+                Token::PlusMinus => "±".to_string(),
+                Token::NotEqual => "≠".to_string(),
+                Token::LessEqual => "≤".to_string(),
+                Token::GreaterEqual => "≥".to_string(),
+                Token::Remove => "∖".to_string(),
+                op => op.to_string(),
+            };
+            let prec = precedence(node);
+            format!("{} {symbol} {}", operand(left, prec), operand(right, prec + 1))
+        }
+        Node::Unary { op: Token::Minus, node, .. } => format!("−{}", operand(node, 10)),
+        Node::Unary { op: Token::Amount, node, .. } => format!("#{}", operand(node, 12)),
+        Node::Unary { node, .. } => format!("…{}", operand(node, 14)),
+        Node::Percent { node, .. } => format!("{}%", operand(node, 10)),
+        Node::Abs { node, .. } => format!("|{}|", unicode(node)),
+        Node::Call { v, args, partial, .. } => call(v, args, *partial),
+        Node::Assign { m: false, id, expr, .. } => match expr.as_ref() {
+            Node::Function { id: params, body, .. } => {
+                let params = match params.as_ref() { Node::Tuple { nodes, .. } => list(nodes, ", "), params => unicode(params) };
+                format!("{}({params}) = {}", unicode(id), unicode(body))
+            }
+            expr => format!("{} = {}", unicode(id), unicode(expr)),
+        }
+        Node::Assign { m: true, id, expr, .. } => format!("{} ≔ {}", unicode(id), operand(expr, 1)),
+        Node::Function { id, body, .. } => format!("{} ↦ {}", operand(id, 3), unicode(body)),
+        Node::Condition { cond, case, default, .. } => format!("{} ? {} : {}", operand(cond, 3), unicode(case), unicode(default)),
+        Node::Import { path, .. } => format!("import \"{path}\""),
+        Node::Block { nodes, .. } => list(nodes, "; "),
+        Node::Named { id, expr, .. } => format!("{}: {}", word(id), unicode(expr)),
+        Node::Error { .. } => "?".to_string(),
+    }
+}
+
+fn param(param: &Param) -> String {
+    match (&param.default, param.rest) {
+        (_, true) => format!("…{}", word(&param.id)),
+        (Some(default), _) => format!("{} ≔ {}", word(&param.id), unicode(default)),
+        (None, _) => word(&param.id),
+    }
+}
+// a vector of equally long vectors, drawn over several lines with aligned columns
+fn matrix(values: &[Value]) -> Option<String> {
+    let rows = values.iter().map(|x| match x { Value::Vector(v) => Some(v.iter().map(value).collect::<Vec<String>>()), _ => None });
+    let rows = rows.collect::<Option<Vec<Vec<String>>>>()?;
+    if rows.len() < 2 || rows[0].is_empty() || rows.iter().any(|x| x.len() != rows[0].len()) { return None }
+    let widths = (0..rows[0].len()).map(|i| rows.iter().map(|x| x[i].chars().count()).max().unwrap()).collect::<Vec<usize>>();
+    let lines = rows.iter().enumerate().map(|(i, row)| {
+        let (open, close) = if i == 0 { ('⎡', '⎤') } else if i == rows.len() - 1 { ('⎣', '⎦') } else { ('⎢', '⎥') };
+        let cells = row.iter().zip(widths.iter()).map(|(x, w)| format!("{x:>w$}")).collect::<Vec<String>>();
+        format!("{open}{}{close}", cells.join(" "))
+    });
+    Some(lines.collect::<Vec<String>>().join("\n"))
+}
+
+/// Renders a value, top-level matrices span several lines.
+pub fn pretty(v: &Value) -> String {
+    if let Value::Vector(values) = v {
+        if let Some(matrix) = matrix(values) { return matrix }
+    }
+    value(v)
+}
+pub fn value(value: &Value) -> String {
+    let list = |values: &[Value], sep: &str| values.iter().map(self::value).collect::<Vec<String>>().join(sep);
+    match value {
+        Value::Number(Number::Int(v)) => v.to_string().replace('-', "−"),
+        Value::Number(Number::Float(v)) => float(*v),
+        Value::Vector(values) => format!("[{}]", list(values, " ")),
+        Value::Set(values) if values.is_empty() => "∅".to_string(),
+        Value::Set(values) => format!("{{{}}}", list(&values.values, ", ")),
+        Value::Tuple(values) => format!("({})", list(values, ", ")),
+        Value::Function(params, body) => match params.as_slice() {
+            [p] if p.default.is_none() && !p.rest => format!("{} ↦ {}", word(&p.id), unicode(body)),
+            params => format!("({}) ↦ {}", params.iter().map(param).collect::<Vec<String>>().join(", "), unicode(body)),
+        }
+        Value::ForeignFunction(params, _) => format!("foreign-function({})",
+            params.iter().map(|x| word(&x.id)).collect::<Vec<String>>().join(", ")),
+        Value::Composed(func, other) => format!("({}) ∘ ({})", self::value(func), self::value(other)),
+        Value::Memo(func, _) | Value::Scoped(func, _) => self::value(func),
+        Value::Compiled(proto) => self::value(&proto.function),
+        Value::Partial(func, args, named) => {
+            let mut args = args.iter().map(self::value).collect::<Vec<String>>();
+            args.extend(named.iter().map(|(id, x)| format!("{}: {}", word(id), self::value(x))));
+            args.push("…".to_string());
+            format!("({})({})", self::value(func), args.join(", "))
+        }
+        Value::Module(path, _) => format!("module({path})"),
+        Value::Str(v) => v.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    fn render(text: &str) -> String {
+        let (tokens, poses) = lex(text, "test").unwrap();
+        unicode(&parse(tokens, poses, "test").unwrap())
+    }
+    fn shown(text: &str) -> String {
+        value(&Engine::new().eval(text).unwrap().unwrap())
+    }
+    #[test]
+    fn expressions() {
+        assert_eq!(render("x^2 + y^(n+1)"), "x² + yⁿ⁺¹");
+        assert_eq!(render("x^(a*b)"), "x^(a · b)");
+        assert_eq!(render("sqrt(x) + sqrt(x + 1)"), "√x + √(x + 1)");
+        assert_eq!(render("alpha_1 * theta + x_max"), "α₁ · θ + xₘₐₓ");
+        assert_eq!(render("PI * r^2"), "π · r²");
+        assert_eq!(render("-3 - x"), "−3 − x");
+        assert_eq!(render("1 +- 2"), "1 ± 2");
+        assert_eq!(render("a != b"), "a ≠ b");
+        assert_eq!(render("a <= b"), "a ≤ b");
+        assert_eq!(render("a -- b"), "a ∖ b");
+        assert_eq!(render("floor(x) + ceil(y)"), "⌊x⌋ + ⌈y⌉");
+        assert_eq!(render("f(1, ..)"), "f(1, …)");
+    }
+    #[test]
+    fn functions_and_builders() {
+        assert_eq!(render("f :: x -> x^2"), "f(x) = x²");
+        assert_eq!(render("x -> x"), "x ↦ x");
+        assert_eq!(render("x < 0 ? -x : x"), "x < 0 ? −x : x");
+        assert_eq!(render("filter(x -> x > 2, s)"), "{x ∈ s | x > 2}");
+        assert_eq!(render("map(x -> x^2, s)"), "{x² | x ∈ s}");
+        assert_eq!(render("integrate(x -> x^2, 0, 1)"), "∫₀¹ x² dx");
+        assert_eq!(render("{}"), "∅");
+    }
+    #[test]
+    fn values() {
+        assert_eq!(shown("{}"), "∅");
+        assert_eq!(shown("{1 2}"), "{1, 2}");
+        assert_eq!(shown("-2.5"), "−2.5");
+        assert_eq!(shown("inf"), "∞");
+        assert_eq!(shown("x -> x^2"), "x ↦ x²");
+        assert_eq!(shown("(x, y := 2) -> x"), "(x, y ≔ 2) ↦ x");
+        assert_eq!(shown("[[1 2] [3 4]]"), "[[1 2] [3 4]]");
+        let matrix = pretty(&Engine::new().eval("[[1 2] [3 4]]").unwrap().unwrap());
+        assert_eq!(matrix.lines().count(), 2);
+    }
+}
@@ -1,381 +1,483 @@
-use std::cmp::min;
-use std::ops::{Add, Sub, Mul, Div, Neg};
-use crate::lexer::Token;
-use crate::set::*;
-use crate::error::*;
-use crate::parser::Node;
-use crate::interpreter::Context;
-
-#[derive(Clone, Debug)]
-pub enum Number { Int(i64), Float(f64) }
-impl Number {
-    pub fn pow(&self, other: &Self) -> Self {
-        match (self, other) {
-            (Number::Int(v1), Number::Int(v2)) => Number::Float((*v1 as f64).powi(*v2 as i32)),
-            (Number::Float(v1), Number::Float(v2)) => Number::Float(v1.powf(*v2)),
-            (Number::Int(v1), Number::Float(v2)) => Number::Float((*v1 as f64).powf(*v2)),
-            (Number::Float(v1), Number::Int(v2)) => Number::Float(v1.powi(*v2 as i32)),
-        }
-    }
-}
-impl std::fmt::Display for Number {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Int(v) => write!(f, "{v}"),
-            Self::Float(v) => write!(f, "{v}"),
-        }
-    }
-}
-impl Add for Number {
-    type Output = Self;
-    fn add(self, other: Self) -> Self::Output {
-        match (self, other) {
-            (Self::Int(v1), Self::Int(v2)) => Self::Int(v1 + v2),
-            (Self::Float(v1), Self::Float(v2)) => Self::Float(v1 + v2),
-            (Self::Int(v1), Self::Float(v2)) => Self::Float(v1 as f64 + v2),
-            (Self::Float(v1), Self::Int(v2)) => Self::Float(v1 + v2 as f64),
-        }
-    }
-}
-impl Sub for Number {
-    type Output = Self;
-    fn sub(self, other: Self) -> Self::Output {
-        match (self, other) {
-            (Self::Int(v1), Self::Int(v2)) => Self::Int(v1 - v2),
-            (Self::Float(v1), Self::Float(v2)) => Self::Float(v1 - v2),
-            (Self::Int(v1), Self::Float(v2)) => Self::Float(v1 as f64 - v2),
-            (Self::Float(v1), Self::Int(v2)) => Self::Float(v1 - v2 as f64),
-        }
-    }
-}
-impl Mul for Number {
-    type Output = Self;
-    fn mul(self, other: Self) -> Self::Output {
-        match (self, other) {
-            (Self::Int(v1), Self::Int(v2)) => Self::Int(v1 * v2),
-            (Self::Float(v1), Self::Float(v2)) => Self::Float(v1 * v2),
-            (Self::Int(v1), Self::Float(v2)) => Self::Float(v1 as f64 * v2),
-            (Self::Float(v1), Self::Int(v2)) => Self::Float(v1 * v2 as f64),
-        }
-    }
-}
-impl Div for Number {
-    type Output = Self;
-    fn div(self, other: Self) -> Self::Output {
-        match (self, other) {
-            (Self::Int(v1), Self::Int(v2)) => Self::Float(v1 as f64 / v2 as f64),
-            (Self::Float(v1), Self::Float(v2)) => Self::Float(v1 / v2),
-            (Self::Int(v1), Self::Float(v2)) => Self::Float(v1 as f64 / v2),
-            (Self::Float(v1), Self::Int(v2)) => Self::Float(v1 / v2 as f64),
-        }
-    }
-}
-impl Neg for Number {
-    type Output = Self;
-    fn neg(self) -> Self::Output {
-        match self {
-            Self::Int(v) => Self::Int(-v),
-            Self::Float(v) => Self::Float(-v),
-        }
-    }
-}
-impl PartialEq for Number {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Self::Int(v1), Self::Int(v2)) => *v1 == *v2,
-            (Self::Float(v1), Self::Float(v2)) => *v1 == *v2,
-            (Self::Float(v1), Self::Int(v2)) => *v1 == *v2 as f64,
-            (Self::Int(v1), Self::Float(v2)) => *v1 as f64 == *v2,
-            _ => false,
-        }
-    }
-}
-impl PartialOrd for Number {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (self, other) {
-            (Self::Int(v1), Self::Int(v2)) => if *v1 < *v2 {
-                Some(std::cmp::Ordering::Less)
-            } else if *v1 > *v2 {
-                Some(std::cmp::Ordering::Greater)
-            } else {
-                Some(std::cmp::Ordering::Equal)
-            }
-            (Self::Float(v1), Self::Float(v2)) => if *v1 < *v2 {
-                Some(std::cmp::Ordering::Less)
-            } else if *v1 > *v2 {
-                Some(std::cmp::Ordering::Greater)
-            } else {
-                Some(std::cmp::Ordering::Equal)
-            }
-            (Self::Int(v1), Self::Float(v2)) => if (*v1 as f64) < *v2 {
-                Some(std::cmp::Ordering::Less)
-            } else if *v1 as f64 > *v2 {
-                Some(std::cmp::Ordering::Greater)
-            } else {
-                Some(std::cmp::Ordering::Equal)
-            }
-            (Self::Float(v1), Self::Int(v2)) => if *v1 < *v2 as f64 {
-                Some(std::cmp::Ordering::Less)
-            } else if *v1 > *v2 as f64 {
-                Some(std::cmp::Ordering::Greater)
-            } else {
-                Some(std::cmp::Ordering::Equal)
-            }
-        }
-    }
-}
-
-#[derive(Clone, Debug, PartialEq)]
-pub enum Type { Number, Vector, Set, Tuple, Function, ForeignFunction }
-impl std::fmt::Display for Type {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Number => write!(f, "number"),
-            Self::Vector => write!(f, "vector"),
-            Self::Set => write!(f, "set"),
-            Self::Tuple => write!(f, "tuple"),
-            Self::Function => write!(f, "function"),
-            Self::ForeignFunction => write!(f, "foreign-function"),
-        }
-    }
-}
-
-type ForeignFunction = fn(&mut Context) -> Result<Value, Error>;
-#[derive(Clone)]
-pub enum Value {
-    Number(Number), Vector(Vec<Self>), Set(Set<Self>), Tuple(Vec<Self>),
-    Function(Vec<String>, Node), ForeignFunction(Vec<String>, ForeignFunction) }
-impl Value {
-    pub fn typ(&self) -> Type {
-        match self {
-            Self::Number(_) => Type::Number,
-            Self::Vector(_) => Type::Vector,
-            Self::Set(_) => Type::Set,
-            Self::Tuple(_) => Type::Tuple,
-            Self::Function(_, _) => Type::Function,
-            Self::ForeignFunction(_, _) => Type::ForeignFunction,
-        }
-    }
-    pub fn unop(&self, op: &Token) -> Option<Self> {
-        match self {
-            Self::Number(v) => match op {
-                Token::Minus => Some(Self::Number(-v.to_owned())),
-                _ => None
-            }
-            Self::Vector(v) => match op {
-                Token::Minus => {
-                    let mut values: Vec<Self> = vec![];
-                    for i in 0..v.len() {
-                        values.push(v[i].unop(op)?);
-                    }
-                    Some(Self::Vector(values))
-                }
-                Token::Amount => {
-                    Some(Self::Number(Number::Int(v.len() as i64)))
-                }
-                _ => None
-            }
-            Self::Set(v) => match op {
-                Token::Minus => {
-                    let mut values: Set<Self> = Set::new();
-                    for i in 0..v.len() {
-                        values.add(v.values[i].unop(op)?);
-                    }
-                    Some(Self::Set(values))
-                }
-                Token::Amount => {
-                    Some(Self::Number(Number::Int(v.len() as i64)))
-                }
-                _ => None
-            }
-            _ => None
-        }
-    }
-    pub fn binop(&self, op: &Token, other: &Self) -> Option<Self> {
-        match (self, other) {
-            (Self::Number(v1), Self::Number(v2)) => match op {
-                Token::Plus => Some(Self::Number(v1.to_owned() + v2.to_owned())),
-                Token::Minus => Some(Self::Number(v1.to_owned() - v2.to_owned())),
-                Token::PlusMinus => Some(Self::Tuple(vec![Self::Number(v1.to_owned() + v2.to_owned()), Self::Number(v1.to_owned() - v2.to_owned())])),
-                Token::Star => Some(Self::Number(v1.to_owned() * v2.to_owned())),
-                Token::Slash => Some(Self::Number(v1.to_owned() / v2.to_owned())),
-                Token::Exponent => Some(Self::Number(v1.pow(v2))),
-                Token::Equal => Some(Self::Number(Number::Int((v1.to_owned() == v2.to_owned()) as i64))),
-                Token::NotEqual => Some(Self::Number(Number::Int((v1.to_owned() != v2.to_owned()) as i64))),
-                Token::Less => Some(Self::Number(Number::Int((v1.to_owned() < v2.to_owned()) as i64))),
-                Token::Greater => Some(Self::Number(Number::Int((v1.to_owned() > v2.to_owned()) as i64))),
-                Token::LessEqual => Some(Self::Number(Number::Int((v1.to_owned() <= v2.to_owned()) as i64))),
-                Token::GreaterEqual => Some(Self::Number(Number::Int((v1.to_owned() >= v2.to_owned()) as i64))),
-                _ => None
-            }
-            (Self::Vector(v1), Self::Vector(v2)) => match op {
-                Token::Concat => {
-                    let mut values: Vec<Self> = v1.clone();
-                    for v in v2.iter() {
-                        values.push(v.clone());
-                    }
-                    Some(Self::Vector(values))
-                }
-                _ => {
-                    let mut values: Vec<Self> = vec![];
-                    for i in 0..min(v1.len(), v2.len()) {
-                        values.push(v1[i].binop(op, &v2[i])?);
-                    }
-                    Some(Self::Vector(values))
-                }
-            }
-            (Self::Set(v1), Self::Set(v2)) => match op {
-                Token::Concat => {
-                    let mut values: Set<Self> = v1.clone();
-                    for v in v2.values.iter() {
-                        values.add(v.clone());
-                    }
-                    Some(Self::Set(values))
-                }
-                Token::Remove => {
-                    let mut values: Set<Self> = v1.clone();
-                    for v in v2.values.iter() {
-                        values.remove(v);
-                    }
-                    Some(Self::Set(values))
-                }
-                _ => {
-                    let mut values: Set<Self> = Set::new();
-                    for i in 0..min(v1.len(), v2.len()) {
-                        values.add(v1.values[i].binop(op, &v2.values[i])?);
-                    }
-                    Some(Self::Set(values))
-                }
-            }
-            (Self::Tuple(v1), Self::Tuple(v2)) => match op {
-                Token::Concat => {
-                    let mut values: Vec<Self> = v1.clone();
-                    for v in v2.iter() {
-                        values.push(v.clone());
-                    }
-                    Some(Self::Tuple(values))
-                }
-                _ => {
-                    let mut values: Vec<Self> = vec![];
-                    for i in 0..min(v1.len(), v2.len()) {
-                        values.push(v1[i].binop(op, &v2[i])?);
-                    }
-                    Some(Self::Tuple(values))
-                }
-            }
-            (Self::Vector(v1), Self::Number(v2)) => match op {
-                Token::Concat => {
-                    let mut values: Vec<Self> = v1.clone();
-                    values.push(other.clone());
-                    Some(Self::Vector(values))
-                }
-                Token::Remove => {
-                    let mut values: Vec<Self> = vec![];
-                    for v in v1.iter() {
-                        if v.to_owned() != Value::Number(v2.clone()) {
-                            values.push(v.clone());
-                        }
-                    }
-                    Some(Self::Vector(values))
-                }
-                _ => {
-                    let mut values: Vec<Self> = vec![];
-                    for i in 0..v1.len() {
-                        values.push(v1[i].binop(op, other)?);
-                    }
-                    Some(Self::Vector(values))
-                }
-            }
-            (Self::Set(v1), Self::Number(v2)) => match op {
-                Token::Concat => {
-                    let mut values: Set<Self> = v1.clone();
-                    values.add(other.clone());
-                    Some(Self::Set(values))
-                }
-                Token::Remove => {
-                    let mut values: Set<Self> = v1.clone();
-                    values.remove(other);
-                    Some(Self::Set(values))
-                }
-                _ => {
-                    let mut values: Set<Self> = Set::new();
-                    for i in 0..v1.len() {
-                        values.add(v1.values[i].binop(op, other)?);
-                    }
-                    Some(Self::Set(values))
-                }
-            }
-            (Self::Tuple(v1), Self::Number(v2)) => match op {
-                Token::Concat => {
-                    let mut values: Vec<Self> = v1.clone();
-                    values.push(other.clone());
-                    Some(Self::Tuple(values))
-                }
-                Token::Remove => {
-                    let mut values: Vec<Self> = v1.clone();
-                    if let Number::Int(idx) = v2 {
-                        values.remove(*idx as usize);
-                        return Some(Self::Tuple(values))
-                    }
-                    None
-                }
-                _ => {
-                    let mut values: Vec<Self> = vec![];
-                    for i in 0..v1.len() {
-                        values.push(v1[i].binop(op, other)?);
-                    }
-                    Some(Self::Tuple(values))
-                }
-            }
-            _ => None
-        }
-    }
-}
-impl std::fmt::Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Number(v) => write!(f, "{v}"),
-            Self::Vector(v) => write!(f, "[{}]", v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ")),
-            Self::Set(v) => write!(f, "{{{}}}", v.values.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ")),
-            Self::Tuple(v) => write!(f, "({})", v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")),
-            Self::Function(params, node) => write!(f, "function({})->{node}", params.join(", ")),
-            Self::ForeignFunction(params, _) => write!(f, "foreign-function({})", params.join(", ")),
-        }
-    }
-}
-impl std::fmt::Debug for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Number(v) => write!(f, "Number({v:?})"),
-            Self::Vector(v) => write!(f, "Vector({v:?})"),
-            Self::Set(v) => write!(f, "Set({v:?})"),
-            Self::Tuple(v) => write!(f, "Tuple({v:?})"),
-            Self::Function(params, node) => write!(f, "Function({params:?}, {node:?})"),
-            Self::ForeignFunction(params, func) => write!(f, "ForeignFunction({params:?}, ...)"),
-        }
-    }
-}
-impl PartialEq for Value {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Self::Number(v1), Self::Number(v2)) => v1 == v2,
-            (Self::Vector(v1), Self::Vector(v2)) => v1 == v2,
-            (Self::Set(v1), Self::Set(v2)) => v1 == v2,
-            _ => false,
-        }
-    }
-}
-impl PartialOrd for Value {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (self, other) {
-            (Self::Number(v1), Self::Number(v2)) => if *v1 < *v2 {
-                Some(std::cmp::Ordering::Less)
-            } else if *v1 > *v2 {
-                Some(std::cmp::Ordering::Greater)
-            } else {
-                Some(std::cmp::Ordering::Equal)
-            }
-            _ => None
-        }
-    }
+use std::cmp::min;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::{Add, Sub, Mul, Div, Neg};
+use crate::lexer::Token;
+use crate::set::*;
+use crate::error::*;
+use crate::parser::Node;
+use crate::interpreter::{Context, Scope};
+use crate::vm::Proto;
+
+#[derive(Clone, Debug)]
+pub enum Number { Int(i64), Float(f64) }
+impl Number {
+    pub fn to_float(&self) -> f64 {
+        match self {
+            Number::Int(v) => *v as f64,
+            Number::Float(v) => *v,
+        }
+    }
+    pub fn pow(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Number::Int(v1), Number::Int(v2)) => Number::Float((*v1 as f64).powi(*v2 as i32)),
+            (Number::Float(v1), Number::Float(v2)) => Number::Float(v1.powf(*v2)),
+            (Number::Int(v1), Number::Float(v2)) => Number::Float((*v1 as f64).powf(*v2)),
+            (Number::Float(v1), Number::Int(v2)) => Number::Float(v1.powi(*v2 as i32)),
+        }
+    }
+}
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(v) => write!(f, "{v}"),
+            Self::Float(v) => write!(f, "{v}"),
+        }
+    }
+}
+impl Add for Number {
+    type Output = Self;
+    fn add(self, other: Self) -> Self::Output {
+        match (self, other) {
+            (Self::Int(v1), Self::Int(v2)) => Self::Int(v1 + v2),
+            (Self::Float(v1), Self::Float(v2)) => Self::Float(v1 + v2),
+            (Self::Int(v1), Self::Float(v2)) => Self::Float(v1 as f64 + v2),
+            (Self::Float(v1), Self::Int(v2)) => Self::Float(v1 + v2 as f64),
+        }
+    }
+}
+impl Sub for Number {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self::Output {
+        match (self, other) {
+            (Self::Int(v1), Self::Int(v2)) => Self::Int(v1 - v2),
+            (Self::Float(v1), Self::Float(v2)) => Self::Float(v1 - v2),
+            (Self::Int(v1), Self::Float(v2)) => Self::Float(v1 as f64 - v2),
+            (Self::Float(v1), Self::Int(v2)) => Self::Float(v1 - v2 as f64),
+        }
+    }
+}
+impl Mul for Number {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self::Output {
+        match (self, other) {
+            (Self::Int(v1), Self::Int(v2)) => Self::Int(v1 * v2),
+            (Self::Float(v1), Self::Float(v2)) => Self::Float(v1 * v2),
+            (Self::Int(v1), Self::Float(v2)) => Self::Float(v1 as f64 * v2),
+            (Self::Float(v1), Self::Int(v2)) => Self::Float(v1 * v2 as f64),
+        }
+    }
+}
+impl Div for Number {
+    type Output = Self;
+    fn div(self, other: Self) -> Self::Output {
+        match (self, other) {
+            (Self::Int(v1), Self::Int(v2)) => Self::Float(v1 as f64 / v2 as f64),
+            (Self::Float(v1), Self::Float(v2)) => Self::Float(v1 / v2),
+            (Self::Int(v1), Self::Float(v2)) => Self::Float(v1 as f64 / v2),
+            (Self::Float(v1), Self::Int(v2)) => Self::Float(v1 / v2 as f64),
+        }
+    }
+}
+impl Neg for Number {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        match self {
+            Self::Int(v) => Self::Int(-v),
+            Self::Float(v) => Self::Float(-v),
+        }
+    }
+}
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Int(v1), Self::Int(v2)) => *v1 == *v2,
+            (Self::Float(v1), Self::Float(v2)) => *v1 == *v2,
+            (Self::Float(v1), Self::Int(v2)) => *v1 == *v2 as f64,
+            (Self::Int(v1), Self::Float(v2)) => *v1 as f64 == *v2,
+        }
+    }
+}
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Self::Int(v1), Self::Int(v2)) => if *v1 < *v2 {
+                Some(std::cmp::Ordering::Less)
+            } else if *v1 > *v2 {
+                Some(std::cmp::Ordering::Greater)
+            } else {
+                Some(std::cmp::Ordering::Equal)
+            }
+            (Self::Float(v1), Self::Float(v2)) => if *v1 < *v2 {
+                Some(std::cmp::Ordering::Less)
+            } else if *v1 > *v2 {
+                Some(std::cmp::Ordering::Greater)
+            } else {
+                Some(std::cmp::Ordering::Equal)
+            }
+            (Self::Int(v1), Self::Float(v2)) => if (*v1 as f64) < *v2 {
+                Some(std::cmp::Ordering::Less)
+            } else if *v1 as f64 > *v2 {
+                Some(std::cmp::Ordering::Greater)
+            } else {
+                Some(std::cmp::Ordering::Equal)
+            }
+            (Self::Float(v1), Self::Int(v2)) => if *v1 < *v2 as f64 {
+                Some(std::cmp::Ordering::Less)
+            } else if *v1 > *v2 as f64 {
+                Some(std::cmp::Ordering::Greater)
+            } else {
+                Some(std::cmp::Ordering::Equal)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type { Number, Vector, Set, Tuple, Function, ForeignFunction, Module, String }
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number => write!(f, "number"),
+            Self::Vector => write!(f, "vector"),
+            Self::Set => write!(f, "set"),
+            Self::Tuple => write!(f, "tuple"),
+            Self::Function => write!(f, "function"),
+            Self::ForeignFunction => write!(f, "foreign-function"),
+            Self::Module => write!(f, "module"),
+            Self::String => write!(f, "string"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param { pub id: String, pub default: Option<Node>, pub rest: bool, pub optional: bool }
+impl Param {
+    pub fn new(id: &str) -> Self { Self { id: id.to_string(), default: None, rest: false, optional: false } }
+    /// A parameter of a native function that may be left out, it is missing from the arguments instead of defaulted.
+    pub fn optional(id: &str) -> Self { Self { optional: true, ..Self::new(id) } }
+}
+impl From<&str> for Param {
+    fn from(id: &str) -> Self { Self::new(id) }
+}
+impl std::fmt::Display for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.rest { return write!(f, "..{}", self.id) }
+        if self.optional { return write!(f, "{}?", self.id) }
+        match &self.default {
+            Some(node) => write!(f, "{} := {node}", self.id),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Key { Int(i64), Float(u64), Vector(Vec<Key>), Set(Vec<Key>), Tuple(Vec<Key>) }
+
+// natives get the path of the code that called them, so the functions they call back into report it
+pub type ForeignFunction = Rc<dyn Fn(&[Value], &mut Context, &str) -> Result<Value, Error>>;
+#[derive(Clone)]
+pub enum Value {
+    Number(Number), Vector(Rc<Vec<Self>>), Set(Rc<Set<Self>>), Tuple(Rc<Vec<Self>>),
+    Function(Rc<Vec<Param>>, Rc<Node>), ForeignFunction(Rc<Vec<Param>>, ForeignFunction),
+    Composed(Rc<Self>, Rc<Self>), Memo(Rc<Self>, Rc<RefCell<HashMap<Vec<Key>, Self>>>), Partial(Rc<Self>, Rc<Vec<Self>>, Rc<Vec<(String, Self)>>),
+    Module(String, Rc<Scope>), Scoped(Rc<Self>, Rc<Scope>), Str(String), Compiled(Rc<Proto>) }
+impl Value {
+    pub fn typ(&self) -> Type {
+        match self {
+            Self::Number(_) => Type::Number,
+            Self::Vector(_) => Type::Vector,
+            Self::Set(_) => Type::Set,
+            Self::Tuple(_) => Type::Tuple,
+            Self::Function(_, _) => Type::Function,
+            Self::ForeignFunction(_, _) => Type::ForeignFunction,
+            Self::Composed(_, _) => Type::Function,
+            Self::Memo(_, _) => Type::Function,
+            Self::Partial(_, _, _) => Type::Function,
+            Self::Module(_, _) => Type::Module,
+            Self::Scoped(func, _) => func.typ(),
+            Self::Str(_) => Type::String,
+            Self::Compiled(_) => Type::Function,
+        }
+    }
+    pub fn callable(&self) -> bool {
+        matches!(self.typ(), Type::Function | Type::ForeignFunction)
+    }
+    pub fn key(&self) -> Option<Key> {
+        match self {
+            Self::Number(Number::Int(v)) => Some(Key::Int(*v)),
+            Self::Number(Number::Float(v)) => if v.fract() == 0.0 && v.abs() < i64::MAX as f64 {
+                Some(Key::Int(*v as i64))
+            } else if v.is_nan() {
+                Some(Key::Float(f64::NAN.to_bits()))
+            } else {
+                Some(Key::Float(v.to_bits()))
+            }
+            Self::Vector(v) => Some(Key::Vector(v.iter().map(|x| x.key()).collect::<Option<Vec<Key>>>()?)),
+            Self::Tuple(v) => Some(Key::Tuple(v.iter().map(|x| x.key()).collect::<Option<Vec<Key>>>()?)),
+            Self::Set(v) => {
+                let mut keys = v.values.iter().map(|x| x.key()).collect::<Option<Vec<Key>>>()?;
+                keys.sort();
+                Some(Key::Set(keys))
+            }
+            _ => None
+        }
+    }
+    pub fn truthy(&self) -> bool {
+        match self {
+            Self::Number(v) => *v != Number::Int(0),
+            Self::Vector(v) | Self::Tuple(v) => !v.is_empty(),
+            Self::Set(v) => !v.is_empty(),
+            _ => true
+        }
+    }
+    pub fn unop(&self, op: &Token) -> Option<Self> {
+        match self {
+            Self::Number(v) => match op {
+                Token::Minus => Some(Self::Number(-v.to_owned())),
+                _ => None
+            }
+            Self::Vector(v) => match op {
+                Token::Minus => {
+                    let mut values: Vec<Self> = vec![];
+                    for i in 0..v.len() {
+                        values.push(v[i].unop(op)?);
+                    }
+                    Some(Self::Vector(values.into()))
+                }
+                Token::Amount => {
+                    Some(Self::Number(Number::Int(v.len() as i64)))
+                }
+                _ => None
+            }
+            Self::Set(v) => match op {
+                Token::Minus => {
+                    let mut values: Set<Self> = Set::new();
+                    for i in 0..v.len() {
+                        values.add(v.values[i].unop(op)?);
+                    }
+                    Some(Self::Set(values.into()))
+                }
+                Token::Amount => {
+                    Some(Self::Number(Number::Int(v.len() as i64)))
+                }
+                _ => None
+            }
+            _ => None
+        }
+    }
+    pub fn binop(&self, op: &Token, other: &Self) -> Option<Self> {
+        if op == &Token::Compose {
+            if self.callable() && other.callable() {
+                return Some(Self::Composed(Rc::new(self.clone()), Rc::new(other.clone())))
+            }
+            return None
+        }
+        match (self, other) {
+            (Self::Number(v1), Self::Number(v2)) => match op {
+                Token::Plus => Some(Self::Number(v1.to_owned() + v2.to_owned())),
+                Token::Minus => Some(Self::Number(v1.to_owned() - v2.to_owned())),
+                Token::PlusMinus => Some(Self::Tuple(vec![Self::Number(v1.to_owned() + v2.to_owned()), Self::Number(v1.to_owned() - v2.to_owned())].into())),
+                Token::Star => Some(Self::Number(v1.to_owned() * v2.to_owned())),
+                Token::Slash => Some(Self::Number(v1.to_owned() / v2.to_owned())),
+                Token::Exponent => Some(Self::Number(v1.pow(v2))),
+                Token::Equal => Some(Self::Number(Number::Int((v1 == v2) as i64))),
+                Token::NotEqual => Some(Self::Number(Number::Int((v1 != v2) as i64))),
+                Token::Less => Some(Self::Number(Number::Int((v1 < v2) as i64))),
+                Token::Greater => Some(Self::Number(Number::Int((v1 > v2) as i64))),
+                Token::LessEqual => Some(Self::Number(Number::Int((v1 <= v2) as i64))),
+                Token::GreaterEqual => Some(Self::Number(Number::Int((v1 >= v2) as i64))),
+                _ => None
+            }
+            (Self::Vector(v1), Self::Vector(v2)) => match op {
+                Token::Concat => {
+                    let mut values: Vec<Self> = v1.to_vec();
+                    for v in v2.iter() {
+                        values.push(v.clone());
+                    }
+                    Some(Self::Vector(values.into()))
+                }
+                _ => {
+                    let mut values: Vec<Self> = vec![];
+                    for i in 0..min(v1.len(), v2.len()) {
+                        values.push(v1[i].binop(op, &v2[i])?);
+                    }
+                    Some(Self::Vector(values.into()))
+                }
+            }
+            (Self::Set(v1), Self::Set(v2)) => match op {
+                Token::Concat => {
+                    let mut values: Set<Self> = (**v1).clone();
+                    for v in v2.values.iter() {
+                        values.add(v.clone());
+                    }
+                    Some(Self::Set(values.into()))
+                }
+                Token::Remove => {
+                    let mut values: Set<Self> = (**v1).clone();
+                    for v in v2.values.iter() {
+                        values.remove(v);
+                    }
+                    Some(Self::Set(values.into()))
+                }
+                _ => {
+                    let mut values: Set<Self> = Set::new();
+                    for i in 0..min(v1.len(), v2.len()) {
+                        values.add(v1.values[i].binop(op, &v2.values[i])?);
+                    }
+                    Some(Self::Set(values.into()))
+                }
+            }
+            (Self::Tuple(v1), Self::Tuple(v2)) => match op {
+                Token::Concat => {
+                    let mut values: Vec<Self> = v1.to_vec();
+                    for v in v2.iter() {
+                        values.push(v.clone());
+                    }
+                    Some(Self::Tuple(values.into()))
+                }
+                _ => {
+                    let mut values: Vec<Self> = vec![];
+                    for i in 0..min(v1.len(), v2.len()) {
+                        values.push(v1[i].binop(op, &v2[i])?);
+                    }
+                    Some(Self::Tuple(values.into()))
+                }
+            }
+            (Self::Vector(v1), Self::Number(v2)) => match op {
+                Token::Concat => {
+                    let mut values: Vec<Self> = v1.to_vec();
+                    values.push(other.clone());
+                    Some(Self::Vector(values.into()))
+                }
+                Token::Remove => {
+                    let mut values: Vec<Self> = vec![];
+                    for v in v1.iter() {
+                        if *v != Value::Number(v2.clone()) {
+                            values.push(v.clone());
+                        }
+                    }
+                    Some(Self::Vector(values.into()))
+                }
+                _ => {
+                    let mut values: Vec<Self> = vec![];
+                    for i in 0..v1.len() {
+                        values.push(v1[i].binop(op, other)?);
+                    }
+                    Some(Self::Vector(values.into()))
+                }
+            }
+            (Self::Set(v1), Self::Number(_)) => match op {
+                Token::Concat => {
+                    let mut values: Set<Self> = (**v1).clone();
+                    values.add(other.clone());
+                    Some(Self::Set(values.into()))
+                }
+                Token::Remove => {
+                    let mut values: Set<Self> = (**v1).clone();
+                    values.remove(other);
+                    Some(Self::Set(values.into()))
+                }
+                _ => {
+                    let mut values: Set<Self> = Set::new();
+                    for i in 0..v1.len() {
+                        values.add(v1.values[i].binop(op, other)?);
+                    }
+                    Some(Self::Set(values.into()))
+                }
+            }
+            (Self::Tuple(v1), Self::Number(v2)) => match op {
+                Token::Concat => {
+                    let mut values: Vec<Self> = v1.to_vec();
+                    values.push(other.clone());
+                    Some(Self::Tuple(values.into()))
+                }
+                Token::Remove => {
+                    let mut values: Vec<Self> = v1.to_vec();
+                    if let Number::Int(idx) = v2 {
+                        values.remove(*idx as usize);
+                        return Some(Self::Tuple(values.into()))
+                    }
+                    None
+                }
+                _ => {
+                    let mut values: Vec<Self> = vec![];
+                    for i in 0..v1.len() {
+                        values.push(v1[i].binop(op, other)?);
+                    }
+                    Some(Self::Tuple(values.into()))
+                }
+            }
+            _ => None
+        }
+    }
+}
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(v) => write!(f, "{v}"),
+            Self::Vector(v) => write!(f, "[{}]", v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ")),
+            Self::Set(v) => write!(f, "{{{}}}", v.values.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ")),
+            Self::Tuple(v) => write!(f, "({})", v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")),
+            Self::Function(params, node) => write!(f, "function({})->{node}", params.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")),
+            Self::ForeignFunction(params, _) => write!(f, "foreign-function({})", params.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")),
+            Self::Composed(func, other) => write!(f, "{func} ∘ {other}"),
+            Self::Memo(func, _) => write!(f, "memo({func})"),
+            Self::Module(path, _) => write!(f, "module({path})"),
+            Self::Scoped(func, _) => write!(f, "{func}"),
+            Self::Str(v) => write!(f, "{v}"),
+            Self::Compiled(proto) => write!(f, "{}", proto.function),
+            Self::Partial(func, args, named) => {
+                let mut args = args.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+                args.extend(named.iter().map(|(id, x)| format!("{id}: {x}")));
+                args.push("..".to_string());
+                write!(f, "{func}({})", args.join(", "))
+            }
+        }
+    }
+}
+impl std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(v) => write!(f, "Number({v:?})"),
+            Self::Vector(v) => write!(f, "Vector({v:?})"),
+            Self::Set(v) => write!(f, "Set({v:?})"),
+            Self::Tuple(v) => write!(f, "Tuple({v:?})"),
+            Self::Function(params, node) => write!(f, "Function({params:?}, {node:?})"),
+            Self::ForeignFunction(params, _) => write!(f, "ForeignFunction({params:?}, ...)"),
+            Self::Composed(func, other) => write!(f, "Composed({func:?}, {other:?})"),
+            Self::Memo(func, cache) => write!(f, "Memo({func:?}, {})", cache.borrow().len()),
+            Self::Module(path, scope) => write!(f, "Module({path:?}, {scope:?})"),
+            Self::Scoped(func, _) => write!(f, "Scoped({func:?}, ...)"),
+            Self::Str(v) => write!(f, "Str({v:?})"),
+            Self::Compiled(proto) => write!(f, "Compiled({:?})", proto.function),
+            Self::Partial(func, args, named) => write!(f, "Partial({func:?}, {args:?}, {named:?})"),
+        }
+    }
+}
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Number(v1), Self::Number(v2)) => v1 == v2,
+            (Self::Vector(v1), Self::Vector(v2)) => v1 == v2,
+            (Self::Set(v1), Self::Set(v2)) => v1 == v2,
+            (Self::Str(v1), Self::Str(v2)) => v1 == v2,
+            _ => false,
+        }
+    }
+}
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Self::Number(v1), Self::Number(v2)) => if *v1 < *v2 {
+                Some(std::cmp::Ordering::Less)
+            } else if *v1 > *v2 {
+                Some(std::cmp::Ordering::Greater)
+            } else {
+                Some(std::cmp::Ordering::Equal)
+            }
+            _ => None
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,640 @@
+use crate::set::*;
+use crate::position::*;
+use crate::error::{self, *};
+use crate::lexer::*;
+use crate::parser::*;
+use crate::value::*;
+use crate::interpreter::{self, Context, Named};
+use std::rc::Rc;
+
+// jumps and slots are indices into the code and locals of the chunk they appear in
+#[derive(Debug, Clone)]
+pub enum Op {
+    Const(usize), Load(usize), Local(usize), Define(usize, bool), SetLocal(usize, bool), Bind(usize), Default(usize, usize),
+    Binary(Token), Unary(Token), Percent, Abs, Field(usize, usize),
+    Vector(usize), Set(usize), Tuple(usize),
+    Jump(usize), JumpUnless(usize), Pop,
+    Call(usize), TailCall(usize), Partial(usize), Function(usize), Return,
+    Eval(usize), Fail(usize),
+}
+
+/// A call site, `names` holds the name of each argument passed by name in source order.
+#[derive(Debug)]
+pub struct Site { pub name: Option<String>, pub pos: Position, pub names: Vec<Option<String>>, pub positional: bool }
+
+#[derive(Debug)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub poses: Vec<Position>,
+    pub consts: Vec<Value>,
+    pub names: Vec<String>,
+    pub locals: Vec<String>,
+    pub sites: Vec<Site>,
+    pub protos: Vec<Rc<Proto>>,
+    pub nodes: Vec<Node>,
+    pub path: String,
+}
+impl Chunk {
+    pub fn new(path: &str) -> Self { Self { code: vec![], poses: vec![], consts: vec![], names: vec![], locals: vec![], sites: vec![], protos: vec![], nodes: vec![], path: path.to_string() } }
+}
+
+/// A compiled function, `function` is the same function for the tree-walker and for printing.
+/// `required` counts the parameters without a default and `rest` tells if the last one collects the others.
+#[derive(Debug)]
+pub struct Proto { pub params: Rc<Vec<Param>>, pub required: usize, pub rest: bool, pub function: Value, pub chunk: Rc<Chunk> }
+
+/// A compiled program, evaluated with `execute`.
+#[derive(Debug, Clone)]
+pub struct Program { pub chunk: Rc<Chunk> }
+
+struct Compiler {
+    chunk: Chunk,
+    // inside a function the parameters and assigned names live in slots instead of the context
+    scoped: bool,
+}
+impl Compiler {
+    fn new(path: &str, scoped: bool) -> Self { Self { chunk: Chunk::new(path), scoped } }
+    fn emit(&mut self, op: Op, pos: Position) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.poses.push(pos);
+        self.chunk.code.len() - 1
+    }
+    // points the jump at `at` to the next instruction
+    fn patch(&mut self, at: usize) {
+        let to = self.chunk.code.len();
+        match &mut self.chunk.code[at] {
+            Op::Jump(target) | Op::JumpUnless(target) | Op::Field(_, target) | Op::Default(_, target) => *target = to,
+            _ => {}
+        }
+    }
+    fn name(&mut self, id: &str) -> usize {
+        if let Some(idx) = self.chunk.names.iter().position(|x| x == id) { return idx }
+        self.chunk.names.push(id.to_string());
+        self.chunk.names.len() - 1
+    }
+    fn local(&self, id: &str) -> Option<usize> {
+        self.chunk.locals.iter().rposition(|x| x == id)
+    }
+    fn constant(&mut self, value: Value) -> usize {
+        self.chunk.consts.push(value);
+        self.chunk.consts.len() - 1
+    }
+    fn node(&mut self, node: &Node) -> usize {
+        self.chunk.nodes.push(node.clone());
+        self.chunk.nodes.len() - 1
+    }
+    fn compile(&mut self, node: &Node, tail: bool) {
+        let pos = node.pos();
+        match node {
+            Node::Int { v, .. } => { let idx = self.constant(Value::Number(Number::Int(*v))); self.emit(Op::Const(idx), pos); }
+            Node::Float { v, .. } => { let idx = self.constant(Value::Number(Number::Float(*v))); self.emit(Op::Const(idx), pos); }
+            Node::Word { v, .. } => match self.local(v) {
+                Some(slot) => { self.emit(Op::Local(slot), pos); }
+                None => { let idx = self.name(v); self.emit(Op::Load(idx), pos); }
+            }
+            Node::Percent { node, .. } => { self.compile(node, false); self.emit(Op::Percent, pos); }
+            Node::Abs { node, .. } => { self.compile(node, false); self.emit(Op::Abs, pos); }
+            Node::Vector { v, .. } => {
+                for n in v.iter() { self.compile(n, false) }
+                self.emit(Op::Vector(v.len()), pos);
+            }
+            Node::Set { v, .. } => {
+                for n in v.values.iter() { self.compile(n, false) }
+                self.emit(Op::Set(v.values.len()), pos);
+            }
+            Node::Tuple { nodes, .. } => {
+                for n in nodes.iter() { self.compile(n, false) }
+                self.emit(Op::Tuple(nodes.len()), pos);
+            }
+            Node::Binary { op: Token::Field, left, right, .. } if matches!(right.as_ref(), Node::Word { .. }) => {
+                let Node::Word { v, .. } = right.as_ref() else { unreachable!() };
+                self.compile(left, false);
+                let idx = self.name(v);
+                // a module answers the field directly, anything else evaluates the word
+                let at = self.emit(Op::Field(idx, 0), pos.clone());
+                self.compile(right, false);
+                self.emit(Op::Binary(Token::Field), pos);
+                self.patch(at);
+            }
+            Node::Binary { op, left, right, .. } => {
+                self.compile(left, false);
+                self.compile(right, false);
+                self.emit(Op::Binary(op.clone()), pos);
+            }
+            Node::Unary { op, node, .. } => { self.compile(node, false); self.emit(Op::Unary(op.clone()), pos); }
+            Node::Call { v, args, partial, .. } => {
+                self.compile(v, false);
+                let mut names: Vec<Option<String>> = vec![];
+                for n in args.iter() {
+                    if let Node::Named { id, expr, .. } = n {
+                        self.compile(expr, false);
+                        names.push(Some(id.clone()));
+                        continue
+                    }
+                    self.compile(n, false);
+                    names.push(None);
+                }
+                let name = match v.as_ref() {
+                    Node::Word { v, .. } => Some(v.clone()),
+                    Node::Binary { op: Token::Field, left, right, .. } => Some(format!("{left}.{right}")),
+                    _ => None
+                };
+                let positional = names.iter().all(Option::is_none);
+                self.chunk.sites.push(Site { name, pos: pos.clone(), names, positional });
+                let site = self.chunk.sites.len() - 1;
+                let op = if *partial { Op::Partial(site) } else if tail && self.scoped { Op::TailCall(site) } else { Op::Call(site) };
+                self.emit(op, pos);
+            }
+            Node::Function { id, body, .. } => self.function(id, body, pos),
+            Node::Condition { cond, case, default, .. } => {
+                self.compile(cond, false);
+                let otherwise = self.emit(Op::JumpUnless(0), pos.clone());
+                self.compile(case, tail);
+                let end = self.emit(Op::Jump(0), pos);
+                self.patch(otherwise);
+                self.compile(default, tail);
+                self.patch(end);
+            }
+            Node::Block { nodes, .. } => {
+                if nodes.is_empty() { self.emit(Op::Tuple(0), pos.clone()); }
+                for (i, n) in nodes.iter().enumerate() {
+                    if i > 0 { self.emit(Op::Pop, pos.clone()); }
+                    self.compile(n, false);
+                }
+            }
+            Node::Assign { m, id, expr, .. } => {
+                self.compile(expr, false);
+                match id.as_ref() {
+                    Node::Word { v, .. } if self.scoped => {
+                        let slot = match self.local(v) {
+                            Some(slot) => slot,
+                            None => { self.chunk.locals.push(v.clone()); self.chunk.locals.len() - 1 }
+                        };
+                        self.emit(Op::SetLocal(slot, *m), pos);
+                    }
+                    Node::Word { v, .. } => { let idx = self.name(v); self.emit(Op::Define(idx, *m), pos); }
+                    id => {
+                        self.emit(Op::Pop, pos.clone());
+                        let idx = self.node(id);
+                        self.emit(Op::Fail(idx), pos);
+                    }
+                }
+            }
+            // imports run through the interpreter, they don't see any slots
+            Node::Import { .. } => { let idx = self.node(node); self.emit(Op::Eval(idx), pos); }
+            Node::Named { .. } | Node::Error { .. } => { let idx = self.node(node); self.emit(Op::Fail(idx), pos); }
+        }
+    }
+    fn function(&mut self, id: &Node, body: &Rc<Node>, pos: Position) {
+        let ids = match id { Node::Tuple { nodes, .. } => nodes.iter().collect(), id => vec![id] };
+        let mut params: Vec<Param> = vec![];
+        for n in ids {
+            match interpreter::param(n) {
+                Ok(param) if !params.last().is_some_and(|p| p.rest) => params.push(param),
+                _ => { let idx = self.node(n); self.emit(Op::Fail(idx), pos); return }
+            }
+        }
+        let mut compiler = Compiler::new(&self.chunk.path, true);
+        compiler.chunk.locals = params.iter().map(|p| p.id.clone()).collect();
+        // defaults see the parameters before them, so they are evaluated on entry
+        for (slot, param) in params.iter().enumerate() {
+            if let Some(default) = &param.default {
+                let at = compiler.emit(Op::Default(slot, 0), default.pos());
+                compiler.compile(default, false);
+                compiler.emit(Op::Bind(slot), default.pos());
+                compiler.patch(at);
+            }
+        }
+        compiler.compile(body, true);
+        compiler.emit(Op::Return, body.pos());
+        let required = params.iter().filter(|p| p.default.is_none() && !p.rest).count();
+        let rest = params.iter().any(|p| p.rest);
+        let params = Rc::new(params);
+        let function = Value::Function(params.clone(), body.clone());
+        self.chunk.protos.push(Rc::new(Proto { params, required, rest, function, chunk: Rc::new(compiler.chunk) }));
+        let idx = self.chunk.protos.len() - 1;
+        self.emit(Op::Function(idx), pos);
+    }
+}
+
+/// Compiles a parsed program.
+pub fn compile(node: &Node, path: &str) -> Program {
+    let mut compiler = Compiler::new(path, false);
+    compiler.compile(node, false);
+    compiler.emit(Op::Return, node.pos());
+    Program { chunk: Rc::new(compiler.chunk) }
+}
+
+// the same checks as `interpreter::bind`, made before any argument is moved into a slot
+fn check(params: &[Param], args: &[Value], named: &[(String, Value)]) -> Result<(), Error> {
+    interpreter::check_named(params, named)?;
+    let given = args.len() + named.len();
+    let min = params.iter().filter(|p| p.default.is_none() && !p.rest).count();
+    let max = if params.iter().any(|p| p.rest) { None } else { Some(params.len()) };
+    let mut positional = 0;
+    for param in params.iter().filter(|p| !p.rest) {
+        let by_name = named.iter().any(|(id, _)| id == &param.id);
+        if positional < args.len() {
+            if by_name { return Err(Error::DuplicateArg(param.id.clone())) }
+            positional += 1;
+        } else if !by_name && param.default.is_none() {
+            return Err(Error::Arity(min, max, given))
+        }
+    }
+    if max.is_some() && positional < args.len() { return Err(Error::Arity(min, max, given)) }
+    Ok(())
+}
+// a local and whether it was bound with `::`
+type Slot = Option<(Value, bool)>;
+
+// fills the slots of the parameters, the ones left empty get their defaults on entry
+fn bind(proto: &Proto, args: Vec<Value>, mut named: Named, slots: &mut Vec<Slot>) {
+    let base = slots.len();
+    slots.resize(base + proto.chunk.locals.len(), None);
+    let mut positional = args.into_iter();
+    for (slot, param) in proto.params.iter().enumerate() {
+        let value = if param.rest {
+            Some(Value::Tuple(positional.by_ref().collect::<Vec<Value>>().into()))
+        } else if let Some(value) = positional.next() {
+            Some(value)
+        } else {
+            named.iter().position(|(id, _)| id == &param.id).map(|idx| named.remove(idx).1)
+        };
+        slots[base + slot] = value.map(|v| (v, false));
+    }
+}
+
+struct Activation {
+    chunk: Rc<Chunk>,
+    params: Rc<Vec<Param>>,
+    pc: usize,
+    stack: usize,
+    slots: usize,
+    // the frame of the call a tail call replaced and how many followed it, an error reads the arguments of
+    // any other call back from the slots of its parameters
+    replaced: Option<error::Frame>,
+    tail: usize,
+}
+impl Activation {
+    fn new(chunk: Rc<Chunk>, params: Rc<Vec<Param>>, stack: usize, slots: usize) -> Self {
+        Self { chunk, params, pc: 0, stack, slots, replaced: None, tail: 0 }
+    }
+}
+enum Flow { Continue, Return(Value) }
+
+struct Machine {
+    stack: Vec<Value>,
+    slots: Vec<Slot>,
+    frames: Vec<Activation>,
+}
+impl Machine {
+    fn new(chunk: Rc<Chunk>, slots: Vec<Slot>) -> Self {
+        Self { stack: vec![], slots, frames: vec![Activation::new(chunk, Rc::new(vec![]), 0, 0)] }
+    }
+    fn execute(&mut self, context: &mut Context) -> Result<Value, Error> {
+        let depth = context.depth();
+        loop {
+            let current = self.frames.len() - 1;
+            let chunk = self.frames[current].chunk.clone();
+            let mut pc = self.frames[current].pc;
+            let flow = self.run(&chunk, &mut pc, context);
+            self.frames[current].pc = pc;
+            match flow {
+                Ok(Flow::Continue) => {}
+                Ok(Flow::Return(value)) => {
+                    let frame = self.frames.pop().unwrap();
+                    self.stack.truncate(frame.stack);
+                    self.slots.truncate(frame.slots);
+                    if self.frames.is_empty() { return Ok(value) }
+                    context.set_depth(context.depth() - 1);
+                    self.stack.push(value);
+                }
+                Err(e) => {
+                    context.set_depth(depth);
+                    return Err(self.trace(e))
+                }
+            }
+        }
+    }
+    // positions the error at the failing instruction and adds a frame for every call it passed through
+    fn trace(&self, e: Error) -> Error {
+        let mut e = e;
+        for (i, frame) in self.frames.iter().enumerate().rev() {
+            e = e.at(frame.chunk.poses[frame.pc.saturating_sub(1)].clone()).elided(frame.tail);
+            if i > 0 {
+                if let Some(call) = frame.replaced.clone().or_else(|| self.call(i)) { e = e.traced(call) }
+            }
+        }
+        e
+    }
+    // the frame of the call that made the activation at `i`, with the arguments still in the slots of its parameters
+    fn call(&self, i: usize) -> Option<error::Frame> {
+        let (frame, caller) = (&self.frames[i], &self.frames[i - 1]);
+        let (Op::Call(site) | Op::TailCall(site)) = &caller.chunk.code[caller.pc - 1] else { return None };
+        let site = &caller.chunk.sites[*site];
+        let slot = |idx: usize| self.slots[frame.slots + idx].as_ref().map(|(value, _)| value.clone());
+        let mut positional = site.names.iter().filter(|name| name.is_none()).count();
+        let mut args: Vec<Value> = vec![];
+        for (idx, param) in frame.params.iter().enumerate() {
+            if positional == 0 { break }
+            match (param.rest, slot(idx)) {
+                (true, Some(Value::Tuple(rest))) => { args.extend(rest.iter().cloned()); break }
+                (false, Some(value)) => { args.push(value); positional -= 1 }
+                _ => break
+            }
+        }
+        let named = site.names.iter().flatten()
+            .filter_map(|id| frame.params.iter().position(|p| &p.id == id).and_then(slot).map(|value| (id.clone(), value)))
+            .collect();
+        Some(error::Frame::Call { name: site.name.clone(), pos: site.pos.clone(), args, named })
+    }
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap()
+    }
+    fn arguments(&mut self, site: &Site) -> (Value, Vec<Value>, Named) {
+        let values = self.stack.split_off(self.stack.len() - site.names.len());
+        let func = self.pop();
+        let mut args: Vec<Value> = vec![];
+        let mut named: Named = vec![];
+        for (value, name) in values.into_iter().zip(site.names.iter()) {
+            match name {
+                Some(id) => named.push((id.clone(), value)),
+                None => args.push(value),
+            }
+        }
+        (func, args, named)
+    }
+    // calls a compiled function with positional arguments by moving them from the stack into its slots,
+    // anything else is left to the general path
+    fn enter(&mut self, site: &Site, tail: bool, context: &mut Context) -> Result<bool, Error> {
+        let start = self.stack.len() - site.names.len();
+        let Value::Compiled(proto) = &self.stack[start - 1] else { return Ok(false) };
+        if !site.positional || proto.rest || site.names.len() > proto.params.len() || site.names.len() < proto.required { return Ok(false) }
+        let proto = proto.clone();
+        if tail {
+            let current = self.frames.len() - 1;
+            if current > 0 && self.frames[current].replaced.is_none() { self.frames[current].replaced = self.call(current) }
+        } else if context.depth() >= context.limits.max_depth {
+            return Ok(false)
+        }
+        let slots = if tail { self.frames.last().unwrap().slots } else { self.slots.len() };
+        self.slots.truncate(slots);
+        self.slots.extend(self.stack.drain(start..).map(|v| Some((v, false))));
+        self.slots.resize(slots + proto.chunk.locals.len(), None);
+        if tail {
+            let frame = self.frames.last_mut().unwrap();
+            self.stack.truncate(frame.stack);
+            frame.chunk = proto.chunk.clone();
+            frame.params = proto.params.clone();
+            frame.tail += 1;
+        } else {
+            self.stack.pop();
+            context.set_depth(context.depth() + 1);
+            self.frames.push(Activation::new(proto.chunk.clone(), proto.params.clone(), self.stack.len(), slots));
+        }
+        Ok(true)
+    }
+    fn run(&mut self, chunk: &Chunk, pc: &mut usize, context: &mut Context) -> Result<Flow, Error> {
+        let base = self.frames.last().unwrap().slots;
+        loop {
+            let op = &chunk.code[*pc];
+            *pc += 1;
+            context.step()?;
+            match op {
+                Op::Const(idx) => self.stack.push(chunk.consts[*idx].clone()),
+                Op::Load(idx) => match context.get(&chunk.names[*idx]) {
+                    Some(value) => self.stack.push(value.clone()),
+                    None => return Err(Error::NotDefined(chunk.names[*idx].clone()))
+                }
+                Op::Local(slot) => match &self.slots[base + slot] {
+                    Some((value, _)) => self.stack.push(value.clone()),
+                    // assigned on a branch that wasn't taken, so the name refers to the context
+                    None => match context.get(&chunk.locals[*slot]) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(Error::NotDefined(chunk.locals[*slot].clone()))
+                    }
+                }
+                Op::Define(idx, m) => {
+                    let (id, value) = (&chunk.names[*idx], self.stack.last().unwrap());
+                    if *m {
+                        context.set(id, value)?;
+                    } else {
+                        context.set_const(id, value)?;
+                    }
+                }
+                Op::SetLocal(slot, m) => {
+                    let id = &chunk.locals[*slot];
+                    let bound = match &self.slots[base + slot] { Some((_, constant)) => Some(*constant), None => context.binding(id) };
+                    match (m, bound) {
+                        (false, Some(_)) => return Err(Error::AlreadyDefined(id.clone())),
+                        (true, Some(true)) => return Err(Error::Immutable(id.clone())),
+                        _ => self.slots[base + slot] = Some((self.stack.last().unwrap().clone(), !m))
+                    }
+                }
+                Op::Bind(slot) => {
+                    let value = self.pop();
+                    self.slots[base + slot] = Some((value, false));
+                }
+                Op::Default(slot, to) => if self.slots[base + slot].is_some() { *pc = *to }
+                Op::Binary(op) => {
+                    let v2 = self.pop();
+                    let v1 = self.pop();
+                    match v1.binop(op, &v2) {
+                        Some(value) => { context.check_size(&value)?; self.stack.push(value) }
+                        None => return Err(Error::IllegalBinaryOperation(op.clone(), v1, v2))
+                    }
+                }
+                Op::Unary(op) => {
+                    let v = self.pop();
+                    match v.unop(op) {
+                        Some(value) => { context.check_size(&value)?; self.stack.push(value) }
+                        None => return Err(Error::IllegalUnaryOperation(op.clone(), v))
+                    }
+                }
+                Op::Percent => match self.pop() {
+                    Value::Number(v) => self.stack.push(Value::Number(v / Number::Int(100))),
+                    value => return Err(Error::ExpectedType(Type::Number, value.typ()))
+                }
+                Op::Abs => match self.pop() {
+                    Value::Number(v) => self.stack.push(Value::Number(if v < Number::Int(0) { -v } else { v })),
+                    value => return Err(Error::ExpectedTypes(vec![Type::Number, Type::Set], value.typ()))
+                }
+                Op::Field(idx, to) => if let Some(Value::Module(module, scope)) = self.stack.last() {
+                    let id = &chunk.names[*idx];
+                    let value = match scope.get(id) {
+                        Some(value) if value.callable() => Value::Scoped(Rc::new(value.clone()), scope.clone()),
+                        Some(value) => value.clone(),
+                        None => return Err(Error::NoField(module.clone(), id.clone()))
+                    };
+                    *self.stack.last_mut().unwrap() = value;
+                    *pc = *to;
+                }
+                Op::Vector(n) => {
+                    let value = Value::Vector(self.stack.split_off(self.stack.len() - n).into());
+                    context.check_size(&value)?;
+                    self.stack.push(value);
+                }
+                Op::Set(n) => {
+                    let mut values: Set<Value> = Set::new();
+                    for value in self.stack.split_off(self.stack.len() - n) { values.add(value) }
+                    let value = Value::Set(values.into());
+                    context.check_size(&value)?;
+                    self.stack.push(value);
+                }
+                Op::Tuple(n) => {
+                    let value = Value::Tuple(self.stack.split_off(self.stack.len() - n).into());
+                    context.check_size(&value)?;
+                    self.stack.push(value);
+                }
+                Op::Jump(to) => *pc = *to,
+                Op::JumpUnless(to) => if !self.pop().truthy() { *pc = *to }
+                Op::Pop => { self.pop(); }
+                Op::Partial(site) => {
+                    let (func, args, named) = self.arguments(&chunk.sites[*site]);
+                    if !func.callable() { return Err(Error::ExpectedType(Type::Function, func.typ())) }
+                    self.stack.push(Value::Partial(Rc::new(func), args.into(), named.into()));
+                }
+                Op::Call(site) | Op::TailCall(site) => {
+                    let tail = matches!(op, Op::TailCall(_));
+                    let site = &chunk.sites[*site];
+                    if self.enter(site, tail, context)? {
+                        if tail { *pc = 0 }
+                        return Ok(Flow::Continue)
+                    }
+                    let (func, args, named) = self.arguments(site);
+                    let traced = |e: Error, args: Vec<Value>, named: Named| e.at(site.pos.clone())
+                        .traced(error::Frame::Call { name: site.name.clone(), pos: site.pos.clone(), args, named });
+                    if let Value::Compiled(proto) = &func {
+                        if let Err(e) = check(&proto.params, &args, &named) { return Err(traced(e, args, named)) }
+                        if tail {
+                            // the callee takes over this frame, so deep tail recursion runs in constant space
+                            let current = self.frames.len() - 1;
+                            if current > 0 && self.frames[current].replaced.is_none() { self.frames[current].replaced = self.call(current) }
+                            let frame = self.frames.last_mut().unwrap();
+                            self.stack.truncate(frame.stack);
+                            self.slots.truncate(frame.slots);
+                            bind(proto, args, named, &mut self.slots);
+                            frame.chunk = proto.chunk.clone();
+                            frame.params = proto.params.clone();
+                            frame.tail += 1;
+                            *pc = 0;
+                            return Ok(Flow::Continue)
+                        }
+                        if context.depth() >= context.limits.max_depth { return Err(Error::RecursionLimit(context.limits.max_depth)) }
+                        context.set_depth(context.depth() + 1);
+                        let slots = self.slots.len();
+                        bind(proto, args, named, &mut self.slots);
+                        self.frames.push(Activation::new(proto.chunk.clone(), proto.params.clone(), self.stack.len(), slots));
+                        return Ok(Flow::Continue)
+                    }
+                    let value = match interpreter::call_with(&func, &args, &named, context, &chunk.path) {
+                        Ok(value) => value,
+                        Err(e) => return Err(traced(e, args, named))
+                    };
+                    context.check_size(&value)?;
+                    if tail { return Ok(Flow::Return(value)) }
+                    self.stack.push(value);
+                }
+                Op::Function(idx) => self.stack.push(Value::Compiled(chunk.protos[*idx].clone())),
+                Op::Return => return Ok(Flow::Return(self.pop())),
+                Op::Eval(idx) => {
+                    let value = interpreter::get(&chunk.nodes[*idx], context, &chunk.path)?;
+                    self.stack.push(value);
+                }
+                Op::Fail(idx) => return Err(Error::UnexpectedNode(chunk.nodes[*idx].clone())),
+            }
+        }
+    }
+}
+
+/// Runs a compiled program against `context`.
+pub fn execute(program: &Program, context: &mut Context) -> Result<Value, Error> {
+    Machine::new(program.chunk.clone(), vec![]).execute(context)
+}
+/// Calls a compiled function, this is how the interpreter and natives reach compiled code.
+pub fn call(proto: &Proto, args: &[Value], named: &[(String, Value)], context: &mut Context) -> Result<Value, Error> {
+    // a function only sees the top-level definitions, not the locals of whoever called it
+    if context.scopes().len() > 1 { return call(proto, args, named, &mut context.frame()) }
+    check(&proto.params, args, named)?;
+    if context.depth() >= context.limits.max_depth { return Err(Error::RecursionLimit(context.limits.max_depth)) }
+    let mut slots: Vec<Slot> = vec![];
+    bind(proto, args.to_vec(), named.to_vec(), &mut slots);
+    context.set_depth(context.depth() + 1);
+    let value = Machine::new(proto.chunk.clone(), slots).execute(context);
+    context.set_depth(context.depth() - 1);
+    value
+}
+
+/// Compiles and runs `text`, the counterpart of `crate::run` on the virtual machine.
+pub fn run(text: &str, context: &mut Context, path: &str) -> Result<Option<Value>, Error> {
+    context.start();
+    let (tokens, poses) = lex(text, path)?;
+    if tokens.is_empty() { return Ok(None) }
+    let node = crate::parser::parse(tokens, poses, path)?;
+    let program = compile(&node, path);
+    context.check_time()?;
+    Ok(Some(execute(&program, context)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Limits;
+    use std::time::Duration;
+
+    // the value, or the first line of the error, on both the tree-walker and the virtual machine
+    fn both(limits: &Limits, text: &str) -> (String, String) {
+        let on = |run: fn(&str, &mut Context, &str) -> Result<Option<Value>, Error>| {
+            let mut context = Context::new();
+            crate::builtins::std_context(&mut context).unwrap();
+            context.limits = limits.clone();
+            match run(text, &mut context, "test") {
+                Ok(value) => value.map_or("".to_string(), |v| v.to_string()),
+                Err(e) => e.to_string().lines().next().unwrap().to_string(),
+            }
+        };
+        (on(crate::run), on(run))
+    }
+    fn agree(limits: Limits, cases: &[&str]) {
+        for text in cases {
+            let (interpreted, compiled) = both(&limits, text);
+            assert_eq!(interpreted, compiled, "{text}");
+        }
+    }
+    #[test]
+    fn calls_agree_with_the_interpreter() {
+        agree(Limits::default(), &[
+            "f := (x, y := 2) -> x * y; f(3) + f(3, 4) + f(y: 5, x: 1)",
+            "f := (x, y := x + 1) -> x * y; f(3)",
+            "f := (n, ..r) -> (n, r); (f(1), f(1, 2), f(1, 2, 3))",
+            "f := (x, y) -> x - y; f(y: 1, 10)",
+            "x := 5; f := y -> y; (f(x := 2), x)",
+            "f := (x, y) -> x; f(1)",
+            "f := (x, y) -> x; f(1, 2, 3)",
+            "f := (x, y) -> x; f(1, z: 2)",
+            "f := (x, y) -> x; f(1, x: 2)",
+            "f := (a, b, c) -> a * 100 + b * 10 + c; f(1, ..)(2, ..)(3)",
+            "f := (a, b := 0) -> a - b; f(b: 1, ..)(10, b: 5)",
+            "fib := memo(n -> n < 2 ? n : fib(n - 1) + fib(n - 2)); fib(60)",
+            "count := (n, acc := 0) -> n = 0 ? acc : count(n - 1, acc + n); count(50000)",
+            "even := n -> n = 0 ? 1 : odd(n - 1); odd := n -> n = 0 ? 0 : even(n - 1); even(2001)",
+            "k := 3; f := x -> x * k; map(f, [1 2 3])",
+            "f := n -> n = 0 ? x : 1 + f(n - 1); f(3)",
+            "1(2)",
+        ]);
+    }
+    #[test]
+    fn limits_agree_with_the_interpreter() {
+        agree(Limits { fuel: Some(100), ..Limits::default() }, &["f := n -> f(n + 1); f(0)", "count := n -> n = 0 ? 0 : count(n - 1); count(1000)"]);
+        agree(Limits { max_size: Some(4), ..Limits::default() }, &["[1 2] ++ [3 4]", "[1 2] ++ [3 4 5]"]);
+        agree(Limits { max_size: Some(3), ..Limits::default() }, &["m := memo(x -> x * 2); m(1) + m(2) + m(3) + m(4)"]);
+        // the interpreter counts nested nodes and the machine nested calls, so only compare clear cases
+        agree(Limits { max_depth: 50, ..Limits::default() }, &[
+            "f := n -> n = 0 ? 0 : 1 + f(n - 1); f(5)",
+            "f := n -> n = 0 ? 0 : 1 + f(n - 1); f(100)",
+            "f := (n, y := 1) -> n = 0 ? 0 : y + f(n - 1, y: 1); f(100)",
+            "count := n -> n = 0 ? 0 : count(n - 1); count(100)",
+        ]);
+        agree(Limits { timeout: Some(Duration::ZERO), ..Limits::default() }, &["1", "f := n -> f(n + 1); f(0)"]);
+    }
+}